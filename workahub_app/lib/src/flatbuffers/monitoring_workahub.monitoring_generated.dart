@@ -201,10 +201,11 @@ class MonitoringPacket {
   InputStats? get input => InputStats.reader.vTableGetNullable(_bc, _bcOffset, 4);
   SystemStats? get system => SystemStats.reader.vTableGetNullable(_bc, _bcOffset, 6);
   int get timestamp => const fb.Int64Reader().vTableGet(_bc, _bcOffset, 8, 0);
+  int get schemaVersion => const fb.Uint16Reader().vTableGet(_bc, _bcOffset, 10, 1);
 
   @override
   String toString() {
-    return 'MonitoringPacket{input: ${input}, system: ${system}, timestamp: ${timestamp}}';
+    return 'MonitoringPacket{input: ${input}, system: ${system}, timestamp: ${timestamp}, schemaVersion: ${schemaVersion}}';
   }
 }
 
@@ -222,7 +223,7 @@ class MonitoringPacketBuilder {
   final fb.Builder fbBuilder;
 
   void begin() {
-    fbBuilder.startTable(3);
+    fbBuilder.startTable(4);
   }
 
   int addInputOffset(int? offset) {
@@ -237,6 +238,10 @@ class MonitoringPacketBuilder {
     fbBuilder.addInt64(2, timestamp);
     return fbBuilder.offset;
   }
+  int addSchemaVersion(int? schemaVersion) {
+    fbBuilder.addUint16(3, schemaVersion);
+    return fbBuilder.offset;
+  }
 
   int finish() {
     return fbBuilder.endTable();
@@ -247,25 +252,29 @@ class MonitoringPacketObjectBuilder extends fb.ObjectBuilder {
   final InputStatsObjectBuilder? _input;
   final SystemStatsObjectBuilder? _system;
   final int? _timestamp;
+  final int? _schemaVersion;
 
   MonitoringPacketObjectBuilder({
     InputStatsObjectBuilder? input,
     SystemStatsObjectBuilder? system,
     int? timestamp,
+    int? schemaVersion,
   })
       : _input = input,
         _system = system,
-        _timestamp = timestamp;
+        _timestamp = timestamp,
+        _schemaVersion = schemaVersion;
 
   /// Finish building, and store into the [fbBuilder].
   @override
   int finish(fb.Builder fbBuilder) {
     final int? inputOffset = _input?.getOrCreateOffset(fbBuilder);
     final int? systemOffset = _system?.getOrCreateOffset(fbBuilder);
-    fbBuilder.startTable(3);
+    fbBuilder.startTable(4);
     fbBuilder.addOffset(0, inputOffset);
     fbBuilder.addOffset(1, systemOffset);
     fbBuilder.addInt64(2, _timestamp);
+    fbBuilder.addUint16(3, _schemaVersion);
     return fbBuilder.endTable();
   }
 
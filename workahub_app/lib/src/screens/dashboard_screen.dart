@@ -1,6 +1,5 @@
 import 'dart:async';
 import 'dart:typed_data';
-import 'package:flat_buffers/flat_buffers.dart' as fb;
 import 'package:flutter/material.dart';
 import 'package:fl_chart/fl_chart.dart';
 import 'package:provider/provider.dart';
@@ -66,23 +65,10 @@ class _DashboardScreenState extends State<DashboardScreen> {
       final blob = await getMonitoringPacketFbs();
       
       // 2. Decode
-      final buffer = fb.BufferContext.fromBytes(blob);
-      final packet = fbs.MonitoringPacket(buffer, 0); // Root starts at 0? Usually root offset is at 0.
-      // Actually, root object is accessed via root accessor. 
-      // The generated code typically has a static method or constructor for root.
-      // Let's rely on standard flatbuffers usage: 
-      // fbs.MonitoringPacket(buffer, buffer.readInt32(0) + 0) ? 
-      // No, usually `new MonitoringPacket(buffer, rootOffset)`
-      // Let's try standard generated read.
-      
-      // Note: Rust flatbuffers adds a u32 size header usually? 
-      // Or `builder.finished_data()` returns the raw buffer.
-      // Standard: read Uint32 at 0 -> offset.
-      
-      // Checking generated code structure implies we might need to verify reading.
-      // Assuming standard:
-      final rootOffset = buffer.readInt32(0); // Offset to root table
-      final data = fbs.MonitoringPacket(buffer, rootOffset);
+      // Rust writes size-prefixed packets: a u32 length, then a buffer tagged "WHMP".
+      final length = ByteData.sublistView(blob).getUint32(0, Endian.little);
+      final frame = Uint8List.sublistView(blob, 4, 4 + length);
+      final data = fbs.MonitoringPacket(frame);
       
       if (data.input != null) {
         setState(() {
@@ -0,0 +1,298 @@
+use flutter_rust_bridge::frb;
+use anyhow::{Result, anyhow};
+use gstreamer::prelude::*;
+use gstreamer::{Device, DeviceMonitor, Element, ElementFactory, Pipeline};
+use crate::api::encoders::try_set_property;
+
+// Optional audio tracks for screen recordings. Each track is its own
+// source -> convert -> resample -> volume chain; the chains are either mixed
+// into one encoded stream or muxed as separate tracks.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AudioSourceKind {
+    Microphone,
+    // What the machine is playing: the output's monitor (Linux) or loopback (Windows)
+    SystemAudio,
+    // audiotestsrc sine tone, so recordings with audio run headlessly in CI
+    Test,
+}
+
+#[derive(Debug, Clone)]
+pub struct AudioDevice {
+    pub id: String,
+    pub name: String,
+    pub kind: AudioSourceKind,
+}
+
+#[derive(Debug, Clone)]
+pub struct AudioTrack {
+    pub kind: AudioSourceKind,
+    // `AudioDevice::id` from `list_audio_devices`; None uses the system default
+    pub device_id: Option<String>,
+    // Can be toggled while recording with `set_audio_track_muted`
+    pub muted: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AudioMixMode {
+    // One track with every source mixed in
+    Mixed,
+    // One track per source, e.g. to keep the microphone apart from meeting audio
+    SeparateTracks,
+}
+
+#[derive(Debug, Clone)]
+pub struct AudioOptions {
+    // Empty records video only
+    pub tracks: Vec<AudioTrack>,
+    pub mix: AudioMixMode,
+    pub bitrate_kbps: u32,
+}
+
+impl Default for AudioOptions {
+    fn default() -> Self {
+        Self {
+            tracks: Vec::new(),
+            mix: AudioMixMode::Mixed,
+            bitrate_kbps: 128,
+        }
+    }
+}
+
+// Picked by the container: AAC for MP4/MKV, Opus for WebM
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum AudioCodec {
+    Aac,
+    Opus,
+}
+
+impl AudioCodec {
+    // In order of preference; all take "bitrate" in bits per second
+    fn encoders(&self) -> &'static [&'static str] {
+        match self {
+            AudioCodec::Aac => &["fdkaacenc", "avenc_aac", "voaacenc", "faac"],
+            AudioCodec::Opus => &["opusenc"],
+        }
+    }
+
+    fn parser(&self) -> Option<&'static str> {
+        match self {
+            AudioCodec::Aac => Some("aacparse"),
+            AudioCodec::Opus => None,
+        }
+    }
+}
+
+// Provider-specific stable id (pulse, wasapi2, v4l2 and osx each name it
+// differently); display names aren't unique across devices.
+pub(crate) fn device_id(device: &Device) -> String {
+    for name in ["internal-name", "device-id", "device-path", "unique-id", "device"] {
+        if device.find_property(name).is_none() {
+            continue;
+        }
+        if let Ok(id) = device.property_value(name).get::<String>() {
+            if !id.is_empty() {
+                return id;
+            }
+        }
+    }
+    device.display_name().to_string()
+}
+
+// PulseAudio and pipewire-pulse expose every output as a ".monitor" source
+fn is_monitor(device: &Device) -> bool {
+    device.properties()
+        .is_some_and(|p| p.get::<String>("device.class").is_ok_and(|class| class == "monitor"))
+}
+
+fn audio_devices() -> Result<Vec<Device>> {
+    gstreamer::init().map_err(|e| anyhow!("Failed to init GStreamer: {}", e))?;
+    let monitor = DeviceMonitor::new();
+    monitor.add_filter(Some("Audio/Source"), None);
+    // wasapi2 captures loopback from output devices
+    if cfg!(target_os = "windows") {
+        monitor.add_filter(Some("Audio/Sink"), None);
+    }
+    monitor.start().map_err(|e| anyhow!("Failed to start device monitor: {}", e))?;
+    let devices = monitor.devices().into_iter().collect();
+    monitor.stop();
+    Ok(devices)
+}
+
+pub fn list_audio_devices() -> Result<Vec<AudioDevice>> {
+    Ok(audio_devices()?.iter().map(|device| {
+        let kind = if device.has_classes("Audio/Sink") || is_monitor(device) {
+            AudioSourceKind::SystemAudio
+        } else {
+            AudioSourceKind::Microphone
+        };
+        AudioDevice {
+            id: device_id(device),
+            name: device.display_name().to_string(),
+            kind,
+        }
+    }).collect())
+}
+
+fn find_device(id: &str) -> Result<Device> {
+    audio_devices()?.into_iter()
+        .find(|device| device_id(device) == id)
+        .ok_or_else(|| anyhow!("Audio device {} not found", id))
+}
+
+fn system_audio_source(device_id: Option<&str>) -> Result<Element> {
+    if cfg!(target_os = "windows") {
+        let mut builder = ElementFactory::make("wasapi2src").property("loopback", true);
+        if let Some(id) = device_id {
+            builder = builder.property("device", id);
+        }
+        return Ok(builder.build()?);
+    }
+    match device_id {
+        Some(id) => Ok(find_device(id)?.create_element(None)?),
+        None if cfg!(target_os = "linux") => Ok(ElementFactory::make("pulsesrc")
+            .property("device", "@DEFAULT_MONITOR@")
+            .build()?),
+        // CoreAudio has no loopback; a virtual device (e.g. BlackHole) must be picked
+        None => Err(anyhow!("System audio on this platform needs a loopback device_id")),
+    }
+}
+
+fn make_source(track: &AudioTrack, index: usize) -> Result<Element> {
+    match track.kind {
+        // A different tone per track so mixed and separate output can be told apart
+        AudioSourceKind::Test => Ok(ElementFactory::make("audiotestsrc")
+            .property("is-live", true)
+            .property("freq", 440.0 * (index + 1) as f64)
+            .build()?),
+        AudioSourceKind::Microphone => match &track.device_id {
+            Some(id) => Ok(find_device(id)?.create_element(None)?),
+            None => Ok(ElementFactory::make("autoaudiosrc").build()?),
+        },
+        AudioSourceKind::SystemAudio => system_audio_source(track.device_id.as_deref()),
+    }
+}
+
+fn make_audio_encoder(codec: AudioCodec, bitrate_kbps: u32) -> Result<Element> {
+    let factory = codec.encoders().iter()
+        .find(|factory| ElementFactory::find(factory).is_some())
+        .ok_or_else(|| anyhow!("No {:?} encoder available (tried {})", codec, codec.encoders().join(", ")))?;
+    let encoder = ElementFactory::make(factory).build()?;
+    try_set_property(&encoder, "bitrate", &(bitrate_kbps * 1000).to_string());
+    Ok(encoder)
+}
+
+// Name of the `volume` element for track `index`, used to mute it at runtime
+pub(crate) fn volume_name(index: usize) -> String {
+    format!("audio_volume_{}", index)
+}
+
+// Adds the configured tracks to `pipeline`, encoded as `codec`, and links them
+// to `muxer`'s audio_%u request pads. Returns the number of audio streams muxed.
+pub(crate) fn add_audio_tracks(pipeline: &Pipeline, muxer: &Element, options: &AudioOptions, codec: AudioCodec) -> Result<u32> {
+    let mut chains = Vec::new();
+    for (index, track) in options.tracks.iter().enumerate() {
+        let src = make_source(track, index)?;
+        let convert = ElementFactory::make("audioconvert").build()?;
+        let resample = ElementFactory::make("audioresample").build()?;
+        let volume = ElementFactory::make("volume")
+            .name(volume_name(index))
+            .property("mute", track.muted)
+            .build()?;
+        pipeline.add_many([&src, &convert, &resample, &volume])?;
+        Element::link_many([&src, &convert, &resample, &volume])?;
+        chains.push(volume);
+    }
+
+    let outputs = if options.mix == AudioMixMode::Mixed && chains.len() > 1 {
+        let mixer = ElementFactory::make("audiomixer").build()?;
+        pipeline.add(&mixer)?;
+        for chain in &chains {
+            chain.link(&mixer)?;
+        }
+        vec![mixer]
+    } else {
+        chains
+    };
+
+    let count = outputs.len() as u32;
+    for output in outputs {
+        let convert = ElementFactory::make("audioconvert").build()?;
+        let encoder = make_audio_encoder(codec, options.bitrate_kbps)?;
+        let queue = ElementFactory::make("queue").build()?;
+        let mut chain = vec![convert, encoder];
+        if let Some(parser) = codec.parser() {
+            chain.push(ElementFactory::make(parser).build()?);
+        }
+        chain.push(queue.clone());
+        pipeline.add_many(&chain)?;
+        Element::link_many(std::iter::once(&output).chain(&chain))?;
+
+        let sink_pad = muxer.request_pad_simple("audio_%u")
+            .ok_or_else(|| anyhow!("Muxer has no audio pads"))?;
+        let src_pad = queue.static_pad("src").ok_or_else(|| anyhow!("Queue has no src pad"))?;
+        src_pad.link(&sink_pad)?;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::media::{start_recording, stop_pipeline, CaptureSource};
+    use crate::api::recording_config::RecordingConfig;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    // Needs GStreamer with the base, good and x264/avenc_aac (or another
+    // H.264/AAC) plugins; every source is a test source.
+
+    fn test_track() -> AudioTrack {
+        AudioTrack { kind: AudioSourceKind::Test, device_id: None, muted: false }
+    }
+
+    // Audio streams in an MP4, counted from the pads qtdemux exposes
+    fn audio_streams(path: &str) -> usize {
+        let pipeline = Pipeline::new();
+        let src = ElementFactory::make("filesrc").property("location", path).build().unwrap();
+        let demux = ElementFactory::make("qtdemux").build().unwrap();
+        pipeline.add_many([&src, &demux]).unwrap();
+        src.link(&demux).unwrap();
+        let (tx, rx) = mpsc::channel();
+        demux.connect_no_more_pads(move |demux| {
+            let audio = demux.src_pads().iter().filter(|pad| pad.name().starts_with("audio_")).count();
+            let _ = tx.send(audio);
+        });
+        pipeline.set_state(gstreamer::State::Paused).unwrap();
+        let audio = rx.recv_timeout(Duration::from_secs(5)).expect("qtdemux found no streams");
+        let _ = pipeline.set_state(gstreamer::State::Null);
+        audio
+    }
+
+    fn record_with_audio(id: &str, mix: AudioMixMode) -> usize {
+        let dir = std::env::temp_dir().join(format!("workahub-{}-{}", id, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = RecordingConfig {
+            source: CaptureSource::Test,
+            audio: AudioOptions { tracks: vec![test_track(), test_track()], mix, bitrate_kbps: 128 },
+            ..RecordingConfig::default()
+        };
+        start_recording(id.to_string(), dir.join("audio.mp4").to_string_lossy().to_string(), config).unwrap();
+        thread::sleep(Duration::from_secs(2));
+        let result = stop_pipeline(id.to_string()).unwrap();
+        let streams = audio_streams(result.final_path.as_deref().expect("no segment written"));
+        let _ = std::fs::remove_dir_all(&dir);
+        streams
+    }
+
+    #[test]
+    fn mixed_sources_record_one_audio_stream() {
+        assert_eq!(record_with_audio("test-audio-mixed", AudioMixMode::Mixed), 1);
+    }
+
+    #[test]
+    fn separate_sources_record_one_audio_stream_each() {
+        assert_eq!(record_with_audio("test-audio-separate", AudioMixMode::SeparateTracks), 2);
+    }
+}
@@ -0,0 +1,297 @@
+use flutter_rust_bridge::frb;
+use anyhow::{Result, anyhow};
+use gstreamer::prelude::*;
+use gstreamer::{Device, DeviceMonitor, Element, ElementFactory, Pad, Pipeline};
+use std::sync::{Arc, Mutex};
+use crate::api::audio::device_id;
+
+// Webcam input, either recorded on its own or composited picture-in-picture
+// over the screen with `compositor`. Cameras are built programmatically since
+// device-backed elements can't be expressed in a launch string.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CameraInput {
+    // autovideosrc
+    Default,
+    // `CameraDevice::id` from `list_cameras`
+    Device { id: String },
+    // Moving videotestsrc pattern, for headless tests
+    Test,
+}
+
+#[derive(Debug, Clone)]
+pub struct CameraDevice {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PipCorner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+#[derive(Debug, Clone)]
+pub struct PipLayout {
+    pub corner: PipCorner,
+    // Overlay width as a share of the screen width, clamped to 5..=50;
+    // height follows the camera's aspect ratio
+    pub width_percent: u32,
+    pub margin_px: u32,
+    pub visible: bool,
+}
+
+impl Default for PipLayout {
+    fn default() -> Self {
+        Self {
+            corner: PipCorner::BottomRight,
+            width_percent: 20,
+            margin_px: 24,
+            visible: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PictureInPicture {
+    pub camera: CameraInput,
+    pub layout: PipLayout,
+}
+
+fn video_devices() -> Result<Vec<Device>> {
+    gstreamer::init().map_err(|e| anyhow!("Failed to init GStreamer: {}", e))?;
+    let monitor = DeviceMonitor::new();
+    monitor.add_filter(Some("Video/Source"), None);
+    monitor.start().map_err(|e| anyhow!("Failed to start device monitor: {}", e))?;
+    // Screen grabbers (d3d11, pipewire) register as Source/Monitor
+    let devices = monitor.devices().into_iter()
+        .filter(|device| !device.has_classes("Source/Monitor"))
+        .collect();
+    monitor.stop();
+    Ok(devices)
+}
+
+pub fn list_cameras() -> Result<Vec<CameraDevice>> {
+    Ok(video_devices()?.iter().map(|device| CameraDevice {
+        id: device_id(device),
+        name: device.display_name().to_string(),
+    }).collect())
+}
+
+fn make_camera_source(input: &CameraInput) -> Result<Element> {
+    match input {
+        CameraInput::Default => Ok(ElementFactory::make("autovideosrc").build()?),
+        CameraInput::Device { id } => {
+            let device = video_devices()?.into_iter()
+                .find(|device| device_id(device) == *id)
+                .ok_or_else(|| anyhow!("Camera {} not found", id))?;
+            Ok(device.create_element(None)?)
+        }
+        CameraInput::Test => Ok(ElementFactory::make("videotestsrc")
+            .property("is-live", true)
+            .property_from_str("pattern", "ball")
+            .build()?),
+    }
+}
+
+// Adds camera -> videoconvert -> queue to `pipeline` and returns the queue,
+// still unlinked downstream
+pub(crate) fn add_camera(pipeline: &Pipeline, input: &CameraInput) -> Result<Element> {
+    let src = make_camera_source(input)?;
+    let convert = ElementFactory::make("videoconvert").build()?;
+    let queue = ElementFactory::make("queue").build()?;
+    pipeline.add_many([&src, &convert, &queue])?;
+    Element::link_many([&src, &convert, &queue])?;
+    Ok(queue)
+}
+
+fn caps_size(pad: &Pad) -> Option<(i32, i32)> {
+    let caps = pad.current_caps()?;
+    let s = caps.structure(0)?;
+    Some((s.get::<i32>("width").ok()?, s.get::<i32>("height").ok()?))
+}
+
+// Camera rectangle (x, y, width, height) on a `canvas` sized output. The width
+// is a share of the canvas and the height follows the camera's aspect ratio;
+// a camera too tall for that is shrunk to fit between the margins.
+fn pip_rect((canvas_w, canvas_h): (i32, i32), (camera_w, camera_h): (i32, i32), layout: &PipLayout) -> (i32, i32, i32, i32) {
+    let margin = layout.margin_px as i32;
+    let mut width = canvas_w * layout.width_percent.clamp(5, 50) as i32 / 100;
+    let mut height = width * camera_h / camera_w.max(1);
+    let max_height = (canvas_h - 2 * margin).max(1);
+    if height > max_height {
+        width = width * max_height / height;
+        height = max_height;
+    }
+    let x = match layout.corner {
+        PipCorner::TopLeft | PipCorner::BottomLeft => margin,
+        PipCorner::TopRight | PipCorner::BottomRight => canvas_w - width - margin,
+    };
+    let y = match layout.corner {
+        PipCorner::TopLeft | PipCorner::TopRight => margin,
+        PipCorner::BottomLeft | PipCorner::BottomRight => canvas_h - height - margin,
+    };
+    (x.max(0), y.max(0), width, height)
+}
+
+// Positions the camera pad inside the compositor's output. A no-op until the
+// output caps are negotiated; the caps notifications call it again then.
+fn relayout(compositor: &Element, pad: &Pad, layout: &PipLayout) {
+    let Some(canvas) = compositor.static_pad("src").and_then(|src| caps_size(&src)) else {
+        return;
+    };
+    let camera = caps_size(pad).unwrap_or((16, 9));
+    let (x, y, width, height) = pip_rect(canvas, camera, layout);
+    pad.set_property("xpos", x);
+    pad.set_property("ypos", y);
+    pad.set_property("width", width);
+    pad.set_property("height", height);
+    pad.set_property("alpha", if layout.visible { 1.0f64 } else { 0.0f64 });
+}
+
+// Camera overlay of a running recording
+pub(crate) struct PipHandle {
+    compositor: Element,
+    pad: Pad,
+    layout: Arc<Mutex<PipLayout>>,
+}
+
+impl PipHandle {
+    pub(crate) fn set_layout(&self, layout: PipLayout) {
+        relayout(&self.compositor, &self.pad, &layout);
+        *self.layout.lock().unwrap() = layout;
+    }
+}
+
+// Links a camera into `compositor` above the screen and keeps it placed per
+// `layout` whenever the screen or camera resolution changes
+pub(crate) fn attach_pip(pipeline: &Pipeline, compositor: &Element, pip: &PictureInPicture) -> Result<PipHandle> {
+    let camera = add_camera(pipeline, &pip.camera)?;
+    let pad = compositor.request_pad_simple("sink_%u")
+        .ok_or_else(|| anyhow!("Compositor has no sink pads"))?;
+    pad.set_property("zorder", 1u32);
+    camera.static_pad("src")
+        .ok_or_else(|| anyhow!("Queue has no src pad"))?
+        .link(&pad)?;
+
+    let layout = Arc::new(Mutex::new(pip.layout.clone()));
+    let output = compositor.static_pad("src").ok_or_else(|| anyhow!("Compositor has no src pad"))?;
+    // Weak refs: the pads own these closures, so strong ones would leak the pipeline
+    for watched in [&output, &pad] {
+        let compositor = compositor.downgrade();
+        let camera_pad = pad.downgrade();
+        let layout = layout.clone();
+        watched.connect_notify(Some("caps"), move |_, _| {
+            if let (Some(compositor), Some(pad)) = (compositor.upgrade(), camera_pad.upgrade()) {
+                relayout(&compositor, &pad, &layout.lock().unwrap());
+            }
+        });
+    }
+
+    Ok(PipHandle {
+        compositor: compositor.clone(),
+        pad,
+        layout,
+    })
+}
+
+// Adds a compositor named "pip_mixer" after `screen` and returns it with the
+// last element of the stage. The output is fixed to the screen's size; left
+// alone the compositor would grow to fit a camera larger than the screen
+// (e.g. a small region).
+pub(crate) fn add_pip_mixer(pipeline: &Pipeline, screen: &Element) -> Result<(Element, Element)> {
+    let queue = ElementFactory::make("queue").build()?;
+    let compositor = ElementFactory::make("compositor")
+        .name("pip_mixer")
+        .property_from_str("background", "black")
+        .build()
+        .map_err(|_| anyhow!("GStreamer element compositor is not available"))?;
+    let size_filter = ElementFactory::make("capsfilter").build()?;
+    let convert = ElementFactory::make("videoconvert").build()?;
+    pipeline.add_many([&queue, &compositor, &size_filter, &convert])?;
+    Element::link_many([screen, &queue, &compositor, &size_filter, &convert])?;
+
+    let screen_pad = queue.static_pad("src").and_then(|src| src.peer())
+        .ok_or_else(|| anyhow!("Screen is not linked to the compositor"))?;
+    let weak_filter = size_filter.downgrade();
+    screen_pad.connect_notify(Some("caps"), move |pad, _| {
+        let (Some(size_filter), Some((width, height))) = (weak_filter.upgrade(), caps_size(pad)) else { return };
+        let caps = gstreamer::Caps::builder("video/x-raw")
+            .field("width", width)
+            .field("height", height)
+            .build();
+        size_filter.set_property("caps", &caps);
+    });
+    Ok((compositor, convert))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gstreamer::{Caps, ClockTime, State};
+    use gstreamer_app::AppSink;
+
+    fn layout(corner: PipCorner, width_percent: u32) -> PipLayout {
+        PipLayout { corner, width_percent, margin_px: 10, visible: true }
+    }
+
+    #[test]
+    fn places_camera_in_each_corner() {
+        let canvas = (1000, 600);
+        let camera = (1280, 720);
+        // 20% of 1000 wide, 16:9
+        assert_eq!(pip_rect(canvas, camera, &layout(PipCorner::TopLeft, 20)), (10, 10, 200, 112));
+        assert_eq!(pip_rect(canvas, camera, &layout(PipCorner::TopRight, 20)), (790, 10, 200, 112));
+        assert_eq!(pip_rect(canvas, camera, &layout(PipCorner::BottomLeft, 20)), (10, 478, 200, 112));
+        assert_eq!(pip_rect(canvas, camera, &layout(PipCorner::BottomRight, 20)), (790, 478, 200, 112));
+    }
+
+    #[test]
+    fn clamps_width_percent() {
+        let canvas = (1000, 600);
+        let camera = (1280, 720);
+        assert_eq!(pip_rect(canvas, camera, &layout(PipCorner::TopLeft, 1)).2, 50);
+        assert_eq!(pip_rect(canvas, camera, &layout(PipCorner::TopLeft, 90)).2, 500);
+    }
+
+    #[test]
+    fn sizes_large_camera_from_the_canvas() {
+        // A 1080p camera over a 320x200 region still gets 50% of the region
+        let rect = pip_rect((320, 200), (1920, 1080), &layout(PipCorner::BottomRight, 50));
+        assert_eq!(rect, (150, 100, 160, 90));
+
+        // A portrait camera too tall for the canvas is shrunk to fit the margins
+        let (x, y, width, height) = pip_rect((320, 200), (1080, 1920), &layout(PipCorner::BottomRight, 50));
+        assert_eq!((y, height), (10, 180));
+        assert_eq!(width, 160 * 180 / 284);
+        assert_eq!(x, 320 - width - 10);
+    }
+
+    // Needs GStreamer with the base plugins
+    #[test]
+    fn mixer_output_keeps_screen_size() {
+        gstreamer::init().unwrap();
+        let pipeline = Pipeline::new();
+        let screen = ElementFactory::make("videotestsrc").property("is-live", true).build().unwrap();
+        let screen_caps = Caps::builder("video/x-raw").field("width", 160i32).field("height", 120i32).build();
+        let screen_filter = ElementFactory::make("capsfilter").property("caps", &screen_caps).build().unwrap();
+        pipeline.add_many([&screen, &screen_filter]).unwrap();
+        screen.link(&screen_filter).unwrap();
+
+        let (compositor, tail) = add_pip_mixer(&pipeline, &screen_filter).unwrap();
+        // The test camera negotiates videotestsrc's default 320x240, larger than the screen
+        let pip = PictureInPicture { camera: CameraInput::Test, layout: PipLayout::default() };
+        attach_pip(&pipeline, &compositor, &pip).unwrap();
+        let sink = AppSink::builder().sync(false).build();
+        pipeline.add(&sink).unwrap();
+        tail.link(&sink).unwrap();
+
+        pipeline.set_state(State::Playing).unwrap();
+        let sample = sink.try_pull_sample(ClockTime::from_seconds(5)).expect("no frame from the mixer");
+        let _ = pipeline.set_state(State::Null);
+        let caps = sample.caps().unwrap().structure(0).unwrap().to_owned();
+        assert_eq!((caps.get::<i32>("width").unwrap(), caps.get::<i32>("height").unwrap()), (160, 120));
+    }
+}
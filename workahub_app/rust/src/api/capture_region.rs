@@ -0,0 +1,246 @@
+use flutter_rust_bridge::frb;
+use anyhow::{Result, anyhow};
+use gstreamer::prelude::*;
+use gstreamer::{Caps, Element, ElementFactory, Pipeline};
+use std::sync::{Arc, Mutex};
+use xcap::Window;
+use crate::api::recording_config::RecordingRegion;
+use crate::api::screenshot::DisplayDescriptor;
+
+// Window lookup for window recordings, and the crop stage that limits a
+// recording to a rectangle which can be moved while recording.
+
+#[derive(Debug, Clone)]
+pub struct WindowDescriptor {
+    pub id: u32,
+    pub title: String,
+    pub app_name: String,
+    // Desktop coordinates in logical pixels, same space as `DisplayDescriptor`
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+// Visible (non-minimized) windows that can be recorded
+pub fn list_windows() -> Result<Vec<WindowDescriptor>> {
+    let windows = Window::all().map_err(|e| anyhow!("Failed to enumerate windows: {}", e))?;
+    Ok(windows.into_iter()
+        .filter(|window| !window.is_minimized().unwrap_or(false))
+        .filter_map(|window| Some(WindowDescriptor {
+            id: window.id().ok()?,
+            title: window.title().unwrap_or_default(),
+            app_name: window.app_name().unwrap_or_default(),
+            x: window.x().ok()?,
+            y: window.y().ok()?,
+            width: window.width().ok()?,
+            height: window.height().ok()?,
+        }))
+        .collect())
+}
+
+// By id if given, otherwise the first window whose title contains `title`
+// (case-insensitive)
+pub(crate) fn find_window(id: Option<u32>, title: Option<&str>) -> Result<WindowDescriptor> {
+    let windows = list_windows()?;
+    match (id, title) {
+        (Some(id), _) => windows.into_iter()
+            .find(|window| window.id == id)
+            .ok_or_else(|| anyhow!("Window {} not found", id)),
+        (None, Some(title)) => {
+            let title = title.to_lowercase();
+            windows.into_iter()
+                .find(|window| window.title.to_lowercase().contains(&title))
+                .ok_or_else(|| anyhow!("No window titled like \"{}\"", title))
+        }
+        (None, None) => Err(anyhow!("A window id or title is required")),
+    }
+}
+
+// The window's rectangle within `display`'s frame, in physical pixels, clipped
+// to the display. Used where the grabber can only capture whole displays.
+pub(crate) fn region_on_display(window: &WindowDescriptor, display: &DisplayDescriptor) -> Result<RecordingRegion> {
+    let scale = display.scale_factor as f64;
+    let left = (window.x - display.x).max(0);
+    let top = (window.y - display.y).max(0);
+    let right = (window.x + window.width as i32).min(display.x + display.width as i32) - display.x;
+    let bottom = (window.y + window.height as i32).min(display.y + display.height as i32) - display.y;
+    if right <= left || bottom <= top {
+        return Err(anyhow!("Window \"{}\" is not on display {}", window.title, display.id));
+    }
+    Ok(RecordingRegion {
+        x: (left as f64 * scale) as u32,
+        y: (top as f64 * scale) as u32,
+        width: ((right - left) as f64 * scale) as u32 & !1,
+        height: ((bottom - top) as f64 * scale) as u32 & !1,
+    })
+}
+
+#[derive(Default)]
+struct CropState {
+    region: Option<RecordingRegion>,
+    // Source frame size, once negotiated
+    frame: Option<(u32, u32)>,
+}
+
+// Crop stage of a running recording: videocrop -> videoscale -> capsfilter
+pub(crate) struct RegionCrop {
+    crop: Element,
+    size_filter: Element,
+    state: Arc<Mutex<CropState>>,
+}
+
+impl RegionCrop {
+    pub(crate) fn set_region(&self, region: Option<RecordingRegion>) {
+        let mut state = self.state.lock().unwrap();
+        state.region = region;
+        apply(&self.crop, &self.size_filter, &state);
+    }
+}
+
+// videocrop takes margins rather than a rectangle, so they can only be worked
+// out once the frame size is known. The output size is fixed by the first
+// crop so the encoder and muxer never see a resolution change; later regions
+// are scaled (letterboxed) into it.
+fn apply(crop: &Element, size_filter: &Element, state: &CropState) {
+    let Some(frame) = state.frame else { return };
+    let ((left, top, right, bottom), (width, height)) = crop_geometry(frame, state.region.as_ref());
+    crop.set_property("left", left as i32);
+    crop.set_property("top", top as i32);
+    crop.set_property("right", right as i32);
+    crop.set_property("bottom", bottom as i32);
+
+    let fixed = size_filter.property::<Option<Caps>>("caps")
+        .and_then(|caps| caps.structure(0).map(|s| s.has_field("width")))
+        .unwrap_or(false);
+    if !fixed {
+        let caps = Caps::builder("video/x-raw")
+            .field("width", width as i32)
+            .field("height", height as i32)
+            .build();
+        size_filter.set_property("caps", &caps);
+    }
+}
+
+// videocrop margins (left, top, right, bottom) for `region`, clamped to the
+// frame, and the output size for that crop, rounded down to the even sizes
+// RecordingRegion::validate asks for
+fn crop_geometry((frame_w, frame_h): (u32, u32), region: Option<&RecordingRegion>) -> ((u32, u32, u32, u32), (u32, u32)) {
+    let (x, y, width, height) = match region {
+        Some(r) => {
+            let x = r.x.min(frame_w.saturating_sub(2));
+            let y = r.y.min(frame_h.saturating_sub(2));
+            (x, y, r.width.min(frame_w - x), r.height.min(frame_h - y))
+        }
+        None => (0, 0, frame_w, frame_h),
+    };
+    let margins = (x, y, frame_w - x - width, frame_h - y - height);
+    (margins, (width & !1, height & !1))
+}
+
+// Adds the crop stage after `upstream` and returns it with its last element.
+// `region` None passes frames through uncropped until `set_region` is called.
+pub(crate) fn add_region_crop(
+    pipeline: &Pipeline,
+    upstream: &Element,
+    region: Option<RecordingRegion>,
+) -> Result<(RegionCrop, Element)> {
+    let crop = ElementFactory::make("videocrop").name("region_crop").build()
+        .map_err(|_| anyhow!("GStreamer element videocrop is not available"))?;
+    let scale = ElementFactory::make("videoscale").property("add-borders", true).build()?;
+    let size_filter = ElementFactory::make("capsfilter").build()?;
+    pipeline.add_many([&crop, &scale, &size_filter])?;
+    Element::link_many([upstream, &crop, &scale, &size_filter])?;
+
+    let state = Arc::new(Mutex::new(CropState { region, frame: None }));
+    let sink = crop.static_pad("sink").ok_or_else(|| anyhow!("videocrop has no sink pad"))?;
+    let weak_crop = crop.downgrade();
+    let weak_filter = size_filter.downgrade();
+    let notify_state = state.clone();
+    sink.connect_notify(Some("caps"), move |pad, _| {
+        let (Some(crop), Some(size_filter)) = (weak_crop.upgrade(), weak_filter.upgrade()) else { return };
+        let Some(caps) = pad.current_caps() else { return };
+        let Some(s) = caps.structure(0) else { return };
+        let (Ok(width), Ok(height)) = (s.get::<i32>("width"), s.get::<i32>("height")) else { return };
+        let mut state = notify_state.lock().unwrap();
+        state.frame = Some((width as u32, height as u32));
+        apply(&crop, &size_filter, &state);
+    });
+
+    let tail = size_filter.clone();
+    Ok((RegionCrop { crop, size_filter, state }, tail))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn display(x: i32, width: u32, height: u32, scale_factor: f32) -> DisplayDescriptor {
+        DisplayDescriptor { id: 1, name: "Test".to_string(), x, y: 0, width, height, scale_factor, is_primary: true }
+    }
+
+    fn window(x: i32, y: i32, width: u32, height: u32) -> WindowDescriptor {
+        WindowDescriptor { id: 7, title: "Editor".to_string(), app_name: "editor".to_string(), x, y, width, height }
+    }
+
+    fn region(x: u32, y: u32, width: u32, height: u32) -> RecordingRegion {
+        RecordingRegion { x, y, width, height }
+    }
+
+    fn assert_region(actual: RecordingRegion, expected: RecordingRegion) {
+        assert_eq!(
+            (actual.x, actual.y, actual.width, actual.height),
+            (expected.x, expected.y, expected.width, expected.height)
+        );
+    }
+
+    #[test]
+    fn clips_window_overhanging_display() {
+        let on_display = region_on_display(&window(1800, -50, 400, 300), &display(0, 1920, 1080, 1.0)).unwrap();
+        assert_region(on_display, region(1800, 0, 120, 250));
+    }
+
+    #[test]
+    fn rejects_window_off_display() {
+        assert!(region_on_display(&window(2000, 100, 400, 300), &display(0, 1920, 1080, 1.0)).is_err());
+        // Touching the edge isn't overlapping
+        assert!(region_on_display(&window(-400, 100, 400, 300), &display(0, 1920, 1080, 1.0)).is_err());
+    }
+
+    #[test]
+    fn scales_to_physical_pixels() {
+        // Second display, right of a 1920 wide one
+        let on_display = region_on_display(&window(2020, 100, 500, 300), &display(1920, 1440, 900, 2.0)).unwrap();
+        assert_region(on_display, region(200, 200, 1000, 600));
+    }
+
+    #[test]
+    fn rounds_odd_sizes_down_to_even() {
+        let on_display = region_on_display(&window(10, 10, 301, 201), &display(0, 1920, 1080, 1.0)).unwrap();
+        assert_region(on_display, region(10, 10, 300, 200));
+        // 301 * 1.5 = 451.5
+        let on_display = region_on_display(&window(10, 10, 301, 201), &display(0, 1920, 1080, 1.5)).unwrap();
+        assert_eq!((on_display.width, on_display.height), (450, 300));
+    }
+
+    #[test]
+    fn crops_whole_frame_without_region() {
+        assert_eq!(crop_geometry((1920, 1080), None), ((0, 0, 0, 0), (1920, 1080)));
+        assert_eq!(crop_geometry((1365, 767), None), ((0, 0, 0, 0), (1364, 766)));
+    }
+
+    #[test]
+    fn crop_margins_for_region_inside_frame() {
+        assert_eq!(crop_geometry((1920, 1080), Some(&region(100, 50, 640, 480))), ((100, 50, 1180, 550), (640, 480)));
+    }
+
+    #[test]
+    fn crop_clamps_region_at_frame_edge() {
+        // Runs past the right and bottom edges
+        assert_eq!(crop_geometry((1920, 1080), Some(&region(1900, 1070, 100, 100))), ((1900, 1070, 0, 0), (20, 10)));
+        // Odd leftover width is rounded down for the output
+        assert_eq!(crop_geometry((1920, 1080), Some(&region(1901, 0, 100, 100))), ((1901, 0, 0, 980), (18, 100)));
+        // Starts past the frame: keeps the last two pixels
+        assert_eq!(crop_geometry((1920, 1080), Some(&region(5000, 5000, 100, 100))), ((1918, 1078, 0, 0), (2, 2)));
+    }
+}
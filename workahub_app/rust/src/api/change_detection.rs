@@ -0,0 +1,72 @@
+use flutter_rust_bridge::frb;
+use image::imageops::{self, FilterType};
+use image::RgbaImage;
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+// Perceptual-hash (dHash) change detection between consecutive screenshots of
+// the same display, so an unchanged screen isn't encoded and uploaded again.
+
+lazy_static! {
+    // Hash of the last capture kept per display; None is the stitched virtual desktop.
+    // Duplicates don't replace it, so slow drift still adds up to a change eventually.
+    static ref LAST_HASHES: Mutex<HashMap<Option<u32>, u64>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Debug, Clone)]
+pub struct ChangeDetection {
+    // Max Hamming distance (0-64) between hashes for a capture to count as a
+    // duplicate. 0 only matches visually identical frames; 5 tolerates a clock
+    // or cursor changing.
+    pub threshold: u32,
+    // Drop duplicates entirely instead of returning them flagged
+    pub drop_duplicates: bool,
+}
+
+impl Default for ChangeDetection {
+    fn default() -> Self {
+        Self {
+            threshold: 5,
+            drop_duplicates: false,
+        }
+    }
+}
+
+// Difference hash: shrink to 9x8 greyscale and record whether each pixel is
+// brighter than its right-hand neighbour.
+pub(crate) fn dhash(image: &RgbaImage) -> u64 {
+    let small = imageops::resize(image, 9, 8, FilterType::Triangle);
+    let luma = imageops::grayscale(&small);
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if luma.get_pixel(x, y)[0] > luma.get_pixel(x + 1, y)[0] {
+                hash |= 1;
+            }
+        }
+    }
+    hash
+}
+
+pub(crate) fn hamming_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+// Compares `hash` with the last kept capture of `display_id`. Returns the
+// distance to it (None for the first capture) and whether it is a duplicate.
+pub(crate) fn check_and_record(display_id: Option<u32>, hash: u64, config: &ChangeDetection) -> (Option<u32>, bool) {
+    let mut last_hashes = LAST_HASHES.lock().unwrap();
+    let distance = last_hashes.get(&display_id).map(|last| hamming_distance(*last, hash));
+    let is_duplicate = distance.is_some_and(|d| d <= config.threshold);
+    if !is_duplicate {
+        last_hashes.insert(display_id, hash);
+    }
+    (distance, is_duplicate)
+}
+
+// Forget previous hashes, e.g. after a break so the first capture is always kept
+pub fn reset_change_detection() {
+    LAST_HASHES.lock().unwrap().clear();
+}
@@ -2,6 +2,7 @@ use rusqlite::{params, Connection, Result};
 use std::path::PathBuf;
 use std::fs;
 use flutter_rust_bridge::frb;
+use sha2::{Digest, Sha256};
 
 // Helper to get db path
 fn get_db_path() -> PathBuf {
@@ -12,13 +13,39 @@ fn get_db_path() -> PathBuf {
     // But since we are likely running from a bundle, let's look for a standard place.
     // For now, let's use the current directory + "goappdata" to match the migration.
     
-    let path = PathBuf::from("goappdata");
+    let path = data_dir();
     if !path.exists() {
         fs::create_dir_all(&path).unwrap_or_default();
     }
     path.join("workahub.db")
 }
 
+// Unit tests that record or capture register artifacts too; they get a
+// per-run store in the temp dir instead of one in the crate directory
+fn data_dir() -> PathBuf {
+    if cfg!(test) {
+        std::env::temp_dir().join(format!("workahub-test-data-{}", std::process::id()))
+    } else {
+        PathBuf::from("goappdata")
+    }
+}
+
+// Captured screenshots/recordings live next to the database until uploaded
+fn get_artifacts_dir(kind: &str) -> PathBuf {
+    let path = data_dir().join("artifacts").join(kind);
+    if !path.exists() {
+        fs::create_dir_all(&path).unwrap_or_default();
+    }
+    path
+}
+
+fn open_db() -> Result<Connection> {
+    let conn = Connection::open(get_db_path())?;
+    // Screenshot scheduler and recordings write from their own threads
+    conn.busy_timeout(std::time::Duration::from_secs(5))?;
+    Ok(conn)
+}
+
 pub fn init_db() -> String {
     let path = get_db_path();
     match Connection::open(&path) {
@@ -54,8 +81,184 @@ pub fn init_db() -> String {
                 [],
             );
             
+            // Files produced locally (screenshots, recording segments) awaiting upload
+            let _ = conn.execute(
+                "CREATE TABLE IF NOT EXISTS artifacts (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    kind TEXT NOT NULL,
+                    path TEXT NOT NULL,
+                    size INTEGER NOT NULL,
+                    sha256 TEXT NOT NULL,
+                    metadata TEXT,
+                    parent_id INTEGER REFERENCES artifacts(id),
+                    created_at INTEGER NOT NULL
+                )",
+                [],
+            );
+
+            // Databases created before thumbnails existed lack parent_id; fails harmlessly otherwise
+            let _ = conn.execute("ALTER TABLE artifacts ADD COLUMN parent_id INTEGER REFERENCES artifacts(id)", []);
+
+            let _ = conn.execute(
+                "CREATE TABLE IF NOT EXISTS upload_queue (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    artifact_id INTEGER NOT NULL REFERENCES artifacts(id),
+                    status TEXT NOT NULL DEFAULT 'pending',
+                    attempts INTEGER NOT NULL DEFAULT 0,
+                    enqueued_at INTEGER NOT NULL
+                )",
+                [],
+            );
+
             format!("Database initialized at {:?}", path)
         },
         Err(e) => format!("Failed to init db: {}", e),
     }
 }
+
+pub struct Artifact {
+    pub id: i64,
+    pub kind: String,
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+    // JSON describing where the artifact came from (display, pipeline, ...)
+    pub metadata: Option<String>,
+    // Set on companion artifacts, e.g. a thumbnail points at its full screenshot
+    pub parent_id: Option<i64>,
+    pub created_at: i64,
+}
+
+pub struct PendingUpload {
+    pub queue_id: i64,
+    pub attempts: u32,
+    pub artifact: Artifact,
+}
+
+// Writes `bytes` into the artifact store and records it
+pub(crate) fn store_artifact(
+    kind: &str,
+    extension: &str,
+    bytes: &[u8],
+    metadata: Option<String>,
+    parent_id: Option<i64>,
+) -> anyhow::Result<Artifact> {
+    let file_name = format!("{}-{}.{}", chrono::Utc::now().timestamp_millis(), uuid::Uuid::new_v4(), extension);
+    let path = get_artifacts_dir(kind).join(file_name);
+    fs::write(&path, bytes)?;
+    insert_artifact(kind, path.to_string_lossy().to_string(), bytes.len() as u64, hex_sha256(bytes), metadata, parent_id)
+}
+
+// Records a file that was already written elsewhere (e.g. by a GStreamer sink)
+pub(crate) fn register_artifact(kind: &str, path: String, metadata: Option<String>) -> anyhow::Result<Artifact> {
+    // Hash in chunks; recording segments can be far larger than a screenshot
+    let mut hasher = Sha256::new();
+    let size = std::io::copy(&mut fs::File::open(&path)?, &mut hasher)?;
+    let sha256 = hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect();
+    insert_artifact(kind, path, size, sha256, metadata, None)
+}
+
+fn hex_sha256(bytes: &[u8]) -> String {
+    Sha256::digest(bytes).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn insert_artifact(
+    kind: &str,
+    path: String,
+    size: u64,
+    sha256: String,
+    metadata: Option<String>,
+    parent_id: Option<i64>,
+) -> anyhow::Result<Artifact> {
+    let created_at = chrono::Utc::now().timestamp_millis();
+    let conn = open_db()?;
+    conn.execute(
+        "INSERT INTO artifacts (kind, path, size, sha256, metadata, parent_id, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![kind, path, size as i64, sha256, metadata, parent_id, created_at],
+    )?;
+
+    Ok(Artifact {
+        id: conn.last_insert_rowid(),
+        kind: kind.to_string(),
+        path,
+        size,
+        sha256,
+        metadata,
+        parent_id,
+        created_at,
+    })
+}
+
+const ARTIFACT_COLUMNS: &str = "a.id, a.kind, a.path, a.size, a.sha256, a.metadata, a.parent_id, a.created_at";
+
+// Reads the `ARTIFACT_COLUMNS` starting at column `first`
+fn artifact_from_row(row: &rusqlite::Row, first: usize) -> Result<Artifact> {
+    Ok(Artifact {
+        id: row.get(first)?,
+        kind: row.get(first + 1)?,
+        path: row.get(first + 2)?,
+        size: row.get::<_, i64>(first + 3)? as u64,
+        sha256: row.get(first + 4)?,
+        metadata: row.get(first + 5)?,
+        parent_id: row.get(first + 6)?,
+        created_at: row.get(first + 7)?,
+    })
+}
+
+pub(crate) fn enqueue_upload(artifact_id: i64) -> anyhow::Result<i64> {
+    let conn = open_db()?;
+    conn.execute(
+        "INSERT INTO upload_queue (artifact_id, enqueued_at) VALUES (?1, ?2)",
+        params![artifact_id, chrono::Utc::now().timestamp_millis()],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+// Oldest first, so Flutter can feed them to `upload_file_to_s3` in order
+pub fn list_pending_uploads(limit: u32) -> anyhow::Result<Vec<PendingUpload>> {
+    let conn = open_db()?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT q.id, q.attempts, {}
+         FROM upload_queue q JOIN artifacts a ON a.id = q.artifact_id
+         WHERE q.status = 'pending'
+         ORDER BY q.enqueued_at, q.id
+         LIMIT ?1",
+        ARTIFACT_COLUMNS
+    ))?;
+    let rows = stmt.query_map(params![limit], |row| {
+        Ok(PendingUpload {
+            queue_id: row.get(0)?,
+            attempts: row.get(1)?,
+            artifact: artifact_from_row(row, 2)?,
+        })
+    })?;
+    Ok(rows.collect::<Result<Vec<_>>>()?)
+}
+
+// Newest first; lets the timeline list e.g. "screenshot_thumbnail" without touching full images
+pub fn list_artifacts(kind: String, since: i64, limit: u32) -> anyhow::Result<Vec<Artifact>> {
+    let conn = open_db()?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM artifacts a
+         WHERE a.kind = ?1 AND a.created_at >= ?2
+         ORDER BY a.created_at DESC, a.id DESC
+         LIMIT ?3",
+        ARTIFACT_COLUMNS
+    ))?;
+    let rows = stmt.query_map(params![kind, since, limit], |row| artifact_from_row(row, 0))?;
+    Ok(rows.collect::<Result<Vec<_>>>()?)
+}
+
+pub fn mark_upload_done(queue_id: i64) -> anyhow::Result<()> {
+    let conn = open_db()?;
+    conn.execute("UPDATE upload_queue SET status = 'done' WHERE id = ?1", params![queue_id])?;
+    Ok(())
+}
+
+// Leaves the entry pending so it is retried on the next pass
+pub fn mark_upload_failed(queue_id: i64) -> anyhow::Result<()> {
+    let conn = open_db()?;
+    conn.execute("UPDATE upload_queue SET attempts = attempts + 1 WHERE id = ?1", params![queue_id])?;
+    Ok(())
+}
@@ -0,0 +1,283 @@
+use flutter_rust_bridge::frb;
+use gstreamer::glib;
+use gstreamer::prelude::*;
+use gstreamer::{ClockTime, Element, ElementFactory, MessageView, Pipeline, State};
+use anyhow::{Result, anyhow};
+use lazy_static::lazy_static;
+use std::sync::Mutex;
+
+// Encoder capability probing. Each known encoder is described once (codec,
+// vendor, property names and units); probing checks the factory exists and
+// that it really negotiates caps and encodes a frame, since hardware plugins
+// are often installed on machines without the matching GPU or driver.
+
+lazy_static! {
+    static ref PROBE_CACHE: Mutex<Option<EncoderProbeReport>> = Mutex::new(None);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VideoCodec {
+    Av1,
+    H265,
+    H264,
+    Vp9,
+    Vp8,
+    Mjpeg,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EncoderVendor {
+    VideoToolbox,
+    Vaapi,
+    Nvenc,
+    Qsv,
+    Amf,
+    MediaFoundation,
+    Software,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BitrateUnit {
+    Kbps,
+    Bps,
+}
+
+#[derive(Debug)]
+pub(crate) struct EncoderSpec {
+    pub factory: &'static str,
+    pub codec: VideoCodec,
+    pub vendor: EncoderVendor,
+    // Name and unit of the bitrate property; None for quality-only encoders (jpegenc)
+    bitrate: Option<(&'static str, BitrateUnit)>,
+    // Max distance between keyframes, in frames
+    keyframe_interval: Option<&'static str>,
+    // Low-latency settings needed for live capture
+    realtime: &'static [(&'static str, &'static str)],
+    // Converts the encoder's byte-stream into what muxers expect
+    pub parser: Option<&'static str>,
+}
+
+impl EncoderSpec {
+    pub fn is_hardware(&self) -> bool {
+        self.vendor != EncoderVendor::Software
+    }
+
+    // Property name/value pairs for this encoder, as strings so they can be
+    // applied with `try_set_property` whatever the property's GType.
+    pub fn properties(&self, bitrate_kbps: u32, keyframe_interval: u32) -> Vec<(&'static str, String)> {
+        let mut props: Vec<(&'static str, String)> = self.realtime.iter().map(|(k, v)| (*k, v.to_string())).collect();
+        if let Some((name, unit)) = self.bitrate {
+            let value = match unit {
+                BitrateUnit::Kbps => bitrate_kbps as u64,
+                BitrateUnit::Bps => bitrate_kbps as u64 * 1000,
+            };
+            props.push((name, value.to_string()));
+        }
+        if let Some(name) = self.keyframe_interval {
+            props.push((name, keyframe_interval.to_string()));
+        }
+        props
+    }
+}
+
+const fn spec(
+    factory: &'static str,
+    codec: VideoCodec,
+    vendor: EncoderVendor,
+    bitrate: Option<(&'static str, BitrateUnit)>,
+    keyframe_interval: Option<&'static str>,
+    realtime: &'static [(&'static str, &'static str)],
+    parser: Option<&'static str>,
+) -> EncoderSpec {
+    EncoderSpec { factory, codec, vendor, bitrate, keyframe_interval, realtime, parser }
+}
+
+use BitrateUnit::{Bps, Kbps};
+use EncoderVendor::*;
+use VideoCodec::*;
+
+// In order of preference: hardware before software, newer codecs first within
+// hardware since they cost nothing extra there.
+pub(crate) static ENCODERS: &[EncoderSpec] = &[
+    // Apple VideoToolbox
+    spec("vtenc_h265_hw", H265, VideoToolbox, Some(("bitrate", Kbps)), Some("max-keyframe-interval"), &[("realtime", "true")], Some("h265parse")),
+    spec("vtenc_h264_hw", H264, VideoToolbox, Some(("bitrate", Kbps)), Some("max-keyframe-interval"), &[("realtime", "true")], Some("h264parse")),
+    spec("vtenc_h265", H265, VideoToolbox, Some(("bitrate", Kbps)), Some("max-keyframe-interval"), &[("realtime", "true")], Some("h265parse")),
+    spec("vtenc_h264", H264, VideoToolbox, Some(("bitrate", Kbps)), Some("max-keyframe-interval"), &[("realtime", "true")], Some("h264parse")),
+    // NVIDIA NVENC
+    spec("nvav1enc", Av1, Nvenc, Some(("bitrate", Kbps)), Some("gop-size"), &[("preset", "p1")], Some("av1parse")),
+    spec("nvh265enc", H265, Nvenc, Some(("bitrate", Kbps)), Some("gop-size"), &[("zerolatency", "true")], Some("h265parse")),
+    spec("nvh264enc", H264, Nvenc, Some(("bitrate", Kbps)), Some("gop-size"), &[("zerolatency", "true")], Some("h264parse")),
+    // Intel Quick Sync
+    spec("qsvav1enc", Av1, Qsv, Some(("bitrate", Kbps)), Some("gop-size"), &[], Some("av1parse")),
+    spec("qsvh265enc", H265, Qsv, Some(("bitrate", Kbps)), Some("gop-size"), &[("low-latency", "true")], Some("h265parse")),
+    spec("qsvh264enc", H264, Qsv, Some(("bitrate", Kbps)), Some("gop-size"), &[("low-latency", "true")], Some("h264parse")),
+    // AMD AMF
+    spec("amfav1enc", Av1, Amf, Some(("bitrate", Kbps)), Some("gop-size"), &[("usage", "low-latency")], Some("av1parse")),
+    spec("amfh265enc", H265, Amf, Some(("bitrate", Kbps)), Some("gop-size"), &[("usage", "low-latency")], Some("h265parse")),
+    spec("amfh264enc", H264, Amf, Some(("bitrate", Kbps)), Some("gop-size"), &[("usage", "low-latency")], Some("h264parse")),
+    // VA-API (the `va` plugin first, then legacy gstreamer-vaapi)
+    spec("vaav1enc", Av1, Vaapi, Some(("bitrate", Kbps)), Some("key-int-max"), &[], Some("av1parse")),
+    spec("vah265enc", H265, Vaapi, Some(("bitrate", Kbps)), Some("key-int-max"), &[], Some("h265parse")),
+    spec("vah264enc", H264, Vaapi, Some(("bitrate", Kbps)), Some("key-int-max"), &[], Some("h264parse")),
+    spec("vaapih265enc", H265, Vaapi, Some(("bitrate", Kbps)), Some("keyframe-period"), &[], Some("h265parse")),
+    spec("vaapih264enc", H264, Vaapi, Some(("bitrate", Kbps)), Some("keyframe-period"), &[], Some("h264parse")),
+    // Windows Media Foundation (may itself be hardware backed)
+    spec("mfh265enc", H265, MediaFoundation, Some(("bitrate", Kbps)), Some("gop-size"), &[("low-latency", "true")], Some("h265parse")),
+    spec("mfh264enc", H264, MediaFoundation, Some(("bitrate", Kbps)), Some("gop-size"), &[("low-latency", "true")], Some("h264parse")),
+    // Software
+    spec("x264enc", H264, Software, Some(("bitrate", Kbps)), Some("key-int-max"), &[("speed-preset", "veryfast"), ("tune", "zerolatency")], Some("h264parse")),
+    spec("openh264enc", H264, Software, Some(("bitrate", Bps)), Some("gop-size"), &[("complexity", "low")], Some("h264parse")),
+    spec("svtav1enc", Av1, Software, Some(("target-bitrate", Kbps)), Some("intra-period-length"), &[], Some("av1parse")),
+    spec("vp9enc", Vp9, Software, Some(("target-bitrate", Bps)), Some("keyframe-max-dist"), &[("deadline", "1"), ("cpu-used", "8")], None),
+    spec("x265enc", H265, Software, Some(("bitrate", Kbps)), Some("key-int-max"), &[("speed-preset", "veryfast"), ("tune", "zerolatency")], Some("h265parse")),
+    spec("vp8enc", Vp8, Software, Some(("target-bitrate", Bps)), Some("keyframe-max-dist"), &[("deadline", "1"), ("cpu-used", "8")], None),
+    spec("jpegenc", Mjpeg, Software, None, None, &[], None),
+];
+
+pub(crate) fn spec_for(factory: &str) -> Option<&'static EncoderSpec> {
+    ENCODERS.iter().find(|spec| spec.factory == factory)
+}
+
+#[derive(Debug, Clone)]
+pub struct EncoderCandidate {
+    pub factory: String,
+    pub codec: VideoCodec,
+    pub vendor: EncoderVendor,
+    pub hardware: bool,
+    // Plugin installed
+    pub available: bool,
+    // Negotiated caps and encoded a test frame
+    pub usable: bool,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct EncoderProbeReport {
+    pub chosen: Option<String>,
+    pub chosen_reason: String,
+    pub candidates: Vec<EncoderCandidate>,
+}
+
+// Applies `value` if the property exists and parses for its type. Plugin
+// versions differ in which tuning properties and enum values they have, and
+// setting an unknown one would panic.
+pub(crate) fn try_set_property(element: &Element, name: &str, value: &str) -> bool {
+    let Some(pspec) = element.find_property(name) else { return false };
+    match glib::Value::deserialize(value, pspec.value_type()) {
+        Ok(v) => {
+            element.set_property_from_value(name, &v);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+pub(crate) fn apply_encoder_properties(encoder: &Element, spec: &EncoderSpec, bitrate_kbps: u32, keyframe_interval: u32) {
+    for (name, value) in spec.properties(bitrate_kbps, keyframe_interval) {
+        if !try_set_property(encoder, name, &value) {
+            println!("Encoder {} ignored {}={}", spec.factory, name, value);
+        }
+    }
+}
+
+// Runs a few test frames through `videotestsrc ! videoconvert ! encoder ! fakesink`
+fn verify_encoder(spec: &EncoderSpec) -> Result<()> {
+    let src = ElementFactory::make("videotestsrc").property("num-buffers", 5i32).build()?;
+    let caps = gstreamer::Caps::builder("video/x-raw")
+        .field("width", 320i32)
+        .field("height", 240i32)
+        .field("framerate", gstreamer::Fraction::new(30, 1))
+        .build();
+    let filter = ElementFactory::make("capsfilter").property("caps", &caps).build()?;
+    let convert = ElementFactory::make("videoconvert").build()?;
+    let encoder = ElementFactory::make(spec.factory).build()?;
+    apply_encoder_properties(&encoder, spec, 1000, 30);
+    let sink = ElementFactory::make("fakesink").build()?;
+
+    let pipeline = Pipeline::new();
+    pipeline.add_many([&src, &filter, &convert, &encoder, &sink])?;
+    Element::link_many([&src, &filter, &convert, &encoder, &sink])
+        .map_err(|_| anyhow!("Could not link {}", spec.factory))?;
+
+    let result = (|| {
+        pipeline.set_state(State::Playing).map_err(|_| anyhow!("Refused to start"))?;
+        let bus = pipeline.bus().ok_or_else(|| anyhow!("Pipeline has no bus"))?;
+        for msg in bus.iter_timed(ClockTime::from_seconds(5)) {
+            match msg.view() {
+                MessageView::Eos(..) => return Ok(()),
+                MessageView::Error(err) => return Err(anyhow!("{}", err.error())),
+                _ => (),
+            }
+        }
+        Err(anyhow!("Timed out encoding test frames"))
+    })();
+    let _ = pipeline.set_state(State::Null);
+    result
+}
+
+fn probe_candidates() -> Vec<EncoderCandidate> {
+    ENCODERS.iter().map(|spec| {
+        let available = ElementFactory::find(spec.factory).is_some();
+        let (usable, reason) = if !available {
+            (false, "Plugin not installed".to_string())
+        } else {
+            match verify_encoder(spec) {
+                Ok(()) => (true, "OK".to_string()),
+                Err(e) => (false, format!("Failed test encode: {}", e)),
+            }
+        };
+        EncoderCandidate {
+            factory: spec.factory.to_string(),
+            codec: spec.codec,
+            vendor: spec.vendor,
+            hardware: spec.is_hardware(),
+            available,
+            usable,
+            reason,
+        }
+    }).collect()
+}
+
+fn build_report(candidates: Vec<EncoderCandidate>, accept: &dyn Fn(&EncoderSpec) -> bool) -> EncoderProbeReport {
+    let chosen = candidates.iter()
+        .filter(|c| c.usable)
+        .find(|c| spec_for(&c.factory).is_some_and(accept));
+    let (chosen, chosen_reason) = match chosen {
+        Some(c) => {
+            let skipped = candidates.iter()
+                .take_while(|other| other.factory != c.factory)
+                .filter(|other| other.available)
+                .map(|other| format!("{} ({})", other.factory, other.reason))
+                .collect::<Vec<_>>();
+            let reason = if skipped.is_empty() {
+                format!("{} is the preferred {:?} encoder", c.factory, c.codec)
+            } else {
+                format!("{} chosen; skipped {}", c.factory, skipped.join(", "))
+            };
+            (Some(c.factory.clone()), reason)
+        }
+        None => (None, "No usable video encoder found".to_string()),
+    };
+    EncoderProbeReport { chosen, chosen_reason, candidates }
+}
+
+// Probes every known encoder (cached after the first run unless `force`) and
+// reports which one recordings will use and why.
+pub fn probe_encoders(force: bool) -> Result<EncoderProbeReport> {
+    gstreamer::init().map_err(|e| anyhow!("Failed to init GStreamer: {}", e))?;
+    let mut cache = PROBE_CACHE.lock().map_err(|e| anyhow!("Lock error: {}", e))?;
+    if force || cache.is_none() {
+        *cache = Some(build_report(probe_candidates(), &|_| true));
+    }
+    Ok(cache.clone().unwrap())
+}
+
+// Best usable encoder accepted by `accept` (e.g. codecs the chosen muxer takes)
+pub(crate) fn select_encoder(accept: &dyn Fn(&EncoderSpec) -> bool) -> Result<(&'static EncoderSpec, String)> {
+    let report = probe_encoders(false)?;
+    let report = build_report(report.candidates, accept);
+    let factory = report.chosen.ok_or_else(|| anyhow!("{}", report.chosen_reason))?;
+    let spec = spec_for(&factory).ok_or_else(|| anyhow!("Unknown encoder {}", factory))?;
+    Ok((spec, report.chosen_reason))
+}
@@ -0,0 +1,196 @@
+use flutter_rust_bridge::frb;
+use anyhow::{Result, anyhow};
+use gstreamer::prelude::*;
+use gstreamer::{Bin, Element, ElementFactory, GhostPad, Object, Pad, PadProbeReturn, PadProbeType, Pipeline, State};
+use reqwest::Url;
+use std::sync::mpsc;
+use std::time::Duration;
+use crate::api::encoders::{apply_encoder_properties, select_encoder, VideoCodec};
+
+// Optional live output of a recording, fed from the recording tee. The branch
+// lives in its own bin so it can be added and removed while the file keeps
+// recording, and so its errors (endpoint down, network gone) can be told apart
+// from errors that end the recording. It encodes separately with software
+// H.264, which every RTMP/SRT ingest accepts and a WebRTC sink could take as is.
+
+// Name of the bin holding the live branch
+pub(crate) const LIVE_BIN: &str = "live_stream";
+
+#[derive(Debug, Clone)]
+pub struct LiveStreamOptions {
+    // rtmp:// or rtmps:// (FLV), srt:// (MPEG-TS), or udp://host:port (MPEG-TS,
+    // e.g. to a local `udpsrc port=5000 ! tsdemux ! ...` receiver)
+    pub url: String,
+    pub bitrate_kbps: u32,
+    pub keyframe_interval_secs: u32,
+}
+
+impl LiveStreamOptions {
+    pub(crate) fn validate(&self) -> Result<()> {
+        LiveProtocol::parse(&self.url)?;
+        if !(100..=50_000).contains(&self.bitrate_kbps) {
+            return Err(anyhow!("Live bitrate must be between 100 and 50000 kbps, got {}", self.bitrate_kbps));
+        }
+        if !(1..=10).contains(&self.keyframe_interval_secs) {
+            return Err(anyhow!("Live keyframe interval must be between 1 and 10 seconds, got {}", self.keyframe_interval_secs));
+        }
+        Ok(())
+    }
+}
+
+pub fn default_live_stream_options(url: String) -> LiveStreamOptions {
+    LiveStreamOptions {
+        url,
+        bitrate_kbps: 2500,
+        keyframe_interval_secs: 2,
+    }
+}
+
+enum LiveProtocol {
+    Rtmp,
+    Srt,
+    Udp { host: String, port: u16 },
+}
+
+impl LiveProtocol {
+    fn parse(url: &str) -> Result<Self> {
+        let parsed = Url::parse(url).map_err(|e| anyhow!("Invalid live stream URL {}: {}", url, e))?;
+        match parsed.scheme() {
+            "rtmp" | "rtmps" => Ok(LiveProtocol::Rtmp),
+            "srt" => Ok(LiveProtocol::Srt),
+            "udp" => {
+                let host = parsed.host_str().ok_or_else(|| anyhow!("UDP stream URL needs a host"))?;
+                let port = parsed.port().ok_or_else(|| anyhow!("UDP stream URL needs a port"))?;
+                Ok(LiveProtocol::Udp { host: host.to_string(), port })
+            }
+            scheme => Err(anyhow!("Unsupported live stream protocol {} (use rtmp, rtmps, srt or udp)", scheme)),
+        }
+    }
+
+    // Muxer and network sink. Sinks don't sync or preroll: the branch joins a
+    // pipeline that is already playing, and a slow endpoint must not hold the
+    // recording back.
+    fn make_output(&self, url: &str) -> Result<(Element, Element)> {
+        let (muxer, sink) = match self {
+            LiveProtocol::Rtmp => (
+                ElementFactory::make("flvmux").property("streamable", true).build(),
+                ElementFactory::make("rtmp2sink").property("location", url).build(),
+            ),
+            LiveProtocol::Srt => (
+                ElementFactory::make("mpegtsmux").build(),
+                ElementFactory::make("srtsink")
+                    .property("uri", url)
+                    .property("wait-for-connection", false)
+                    .build(),
+            ),
+            LiveProtocol::Udp { host, port } => (
+                ElementFactory::make("mpegtsmux").build(),
+                ElementFactory::make("udpsink")
+                    .property("host", host.as_str())
+                    .property("port", *port as i32)
+                    .build(),
+            ),
+        };
+        let muxer = muxer.map_err(|e| anyhow!("Live stream muxer is not available: {}", e))?;
+        let sink = sink.map_err(|e| anyhow!("Live stream sink is not available: {}", e))?;
+        sink.set_property("sync", false);
+        sink.set_property("async", false);
+        Ok((muxer, sink))
+    }
+}
+
+// Live branch of a running recording
+pub(crate) struct LiveBranch {
+    bin: Bin,
+    tee_pad: Pad,
+    pub(crate) url: String,
+}
+
+// Builds the live branch and links it to a new `tee` pad. Works before the
+// pipeline starts and while it is playing.
+pub(crate) fn attach_live_stream(pipeline: &Pipeline, tee: &Element, options: &LiveStreamOptions, fps: u32) -> Result<LiveBranch> {
+    options.validate()?;
+    let protocol = LiveProtocol::parse(&options.url)?;
+    let (spec, _) = select_encoder(&|spec| spec.codec == VideoCodec::H264 && !spec.is_hardware())
+        .map_err(|e| anyhow!("Live streaming needs a software H.264 encoder: {}", e))?;
+
+    // A dead endpoint makes the sink fail, and the flow error it returns would
+    // travel back through the tee and stop the recording too. errorignore
+    // turns it into OK at the branch entry; the error message still reaches
+    // the bus, where the recording watcher ends the stream.
+    let guard = ElementFactory::make("errorignore")
+        .property("ignore-error", true)
+        .property("ignore-notlinked", true)
+        .property("ignore-notnegotiated", true)
+        .property_from_str("convert-to", "ok")
+        .build()
+        .map_err(|_| anyhow!("GStreamer element errorignore is not available"))?;
+    // Drops old frames rather than blocking the tee when the network stalls
+    let queue = ElementFactory::make("queue")
+        .property_from_str("leaky", "downstream")
+        .property("max-size-buffers", 0u32)
+        .property("max-size-bytes", 0u32)
+        .property("max-size-time", gstreamer::ClockTime::from_seconds(2).nseconds())
+        .build()?;
+    let convert = ElementFactory::make("videoconvert").build()?;
+    // 4:2:0 is all FLV and most players accept
+    let caps = gstreamer::Caps::builder("video/x-raw").field("format", "I420").build();
+    let filter = ElementFactory::make("capsfilter").property("caps", &caps).build()?;
+    let encoder = ElementFactory::make(spec.factory).build()
+        .map_err(|_| anyhow!("GStreamer element {} is not available", spec.factory))?;
+    apply_encoder_properties(&encoder, spec, options.bitrate_kbps, options.keyframe_interval_secs * fps);
+    // Repeat SPS/PPS on every keyframe so receivers can join mid-stream
+    let parser = ElementFactory::make("h264parse").property("config-interval", -1i32).build()?;
+    let (muxer, sink) = protocol.make_output(&options.url)?;
+
+    let bin = Bin::with_name(LIVE_BIN);
+    let chain = [&guard, &queue, &convert, &filter, &encoder, &parser, &muxer, &sink];
+    bin.add_many(chain)?;
+    Element::link_many(chain).map_err(|_| anyhow!("Failed to link the live stream branch"))?;
+    let target = guard.static_pad("sink").ok_or_else(|| anyhow!("errorignore has no sink pad"))?;
+    let ghost = GhostPad::with_target(&target)?;
+    bin.add_pad(&ghost)?;
+
+    pipeline.add(&bin)?;
+    let tee_pad = tee.request_pad_simple("src_%u").ok_or_else(|| anyhow!("Tee has no src pads"))?;
+    if let Err(e) = tee_pad.link(&ghost) {
+        tee.release_request_pad(&tee_pad);
+        let _ = pipeline.remove(&bin);
+        return Err(anyhow!("Failed to link the live stream branch: {}", e));
+    }
+    bin.sync_state_with_parent()?;
+
+    Ok(LiveBranch {
+        bin,
+        tee_pad,
+        url: options.url.clone(),
+    })
+}
+
+// Unlinks the branch once the tee isn't pushing into it, then shuts it down.
+// The rest of the pipeline keeps running.
+pub(crate) fn detach_live_stream(pipeline: &Pipeline, tee: &Element, branch: LiveBranch) {
+    let (unlinked_tx, unlinked_rx) = mpsc::channel();
+    branch.tee_pad.add_probe(PadProbeType::IDLE, move |pad, _| {
+        if let Some(peer) = pad.peer() {
+            let _ = pad.unlink(&peer);
+        }
+        let _ = unlinked_tx.send(());
+        PadProbeReturn::Remove
+    });
+    // The probe runs right away on an idle pad; a stalled one is unlinked regardless
+    if unlinked_rx.recv_timeout(Duration::from_secs(2)).is_err() {
+        if let Some(peer) = branch.tee_pad.peer() {
+            let _ = branch.tee_pad.unlink(&peer);
+        }
+    }
+    let _ = branch.bin.set_state(State::Null);
+    let _ = pipeline.remove(&branch.bin);
+    tee.release_request_pad(&branch.tee_pad);
+}
+
+// Whether a bus message from `src` came from inside the live branch
+pub(crate) fn is_live_stream_element(src: &Object) -> bool {
+    std::iter::successors(Some(src.clone()), |obj| obj.parent())
+        .any(|obj| obj.name() == LIVE_BIN)
+}
@@ -1,55 +1,219 @@
 use flutter_rust_bridge::frb;
 use gstreamer::prelude::*;
-use gstreamer::{Element, ElementFactory, Pipeline, State, Caps};
+use gstreamer::{Element, ElementFactory, Pipeline, State, Caps, ClockTime, MessageType, MessageView};
 use gstreamer_app::AppSink;
 use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
 use std::collections::HashMap;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
 use anyhow::{Result, anyhow};
 use lazy_static::lazy_static;
+use serde_json::json;
+use crate::api::audio::{add_audio_tracks, volume_name};
+use crate::api::camera::{add_camera, add_pip_mixer, attach_pip, CameraInput, PipHandle, PipLayout};
+use crate::api::recording_config::{RecordingConfig, RecordingRegion};
+use crate::api::capture_region::{add_region_crop, find_window, region_on_display, RegionCrop};
+use crate::api::screenshot::list_displays;
+use crate::api::thumbnails::{render_video_thumbnail, VideoThumbnailOptions};
+use crate::api::live_stream::{attach_live_stream, detach_live_stream, is_live_stream_element, LiveBranch, LiveStreamOptions};
+use crate::api::db::{enqueue_upload, register_artifact};
+use crate::frb_generated::StreamSink;
+use crate::api::encoders::{apply_encoder_properties, probe_encoders, select_encoder, try_set_property, EncoderSpec, EncoderVendor};
 
 // Global state to manage active pipelines and hardware resources
 lazy_static! {
     static ref PIPELINE_MANAGER: Arc<Mutex<PipelineManager>> = Arc::new(Mutex::new(PipelineManager::new()));
 }
 
+// What to do when a new recording would exceed the hardware encoder budget
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GpuBudgetPolicy {
+    // Record with the best software encoder instead
+    FallbackToSoftware,
+    // Refuse to start the recording
+    Reject,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RecordingState {
+    // Built, waiting for the pipeline to reach Playing
+    Starting,
+    Recording,
+    Paused,
+    // EOS sent, waiting for the last segment to be finalized
+    Stopping,
+    Stopped,
+    // Torn down after a pipeline error; stays listed until stopped
+    Failed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RecordingEventKind {
+    StateChanged,
+    SegmentClosed,
+    // Live branch added (message: URL) or removed (message: the error, if it failed)
+    LiveStreamStarted,
+    LiveStreamStopped,
+    Warning,
+    Error,
+}
+
+// Pushed to every `recording_events` subscriber
+#[derive(Debug, Clone)]
+pub struct RecordingEvent {
+    pub id: String,
+    pub kind: RecordingEventKind,
+    // State after the event
+    pub state: RecordingState,
+    // Segment path for SegmentClosed, URL or error for the live stream events,
+    // GStreamer message for Warning/Error
+    pub message: Option<String>,
+    pub timestamp: i64,
+}
+
+pub struct RecordingStatus {
+    pub id: String,
+    pub state: RecordingState,
+    pub encoder: String,
+    pub uses_gpu: bool,
+    pub started_at: i64,
+    // Total time spent paused, including the current pause
+    pub paused_ms: u64,
+    pub last_error: Option<String>,
+    pub warning_count: u32,
+    // Endpoint currently streamed to, if any
+    pub live_stream_url: Option<String>,
+}
+
+struct ActivePipeline {
+    pipeline: Pipeline,
+    encoder: &'static str,
+    uses_gpu: bool,
+    state: RecordingState,
+    started_at: i64,
+    paused_at: Option<i64>,
+    paused_total_ms: u64,
+    last_error: Option<String>,
+    warning_count: u32,
+    // Camera overlay, if recording with picture-in-picture
+    pip: Option<PipHandle>,
+    // Encoder reads the source's CVPixelBuffers directly, with no system-memory
+    // stages (crop, videorate, snapshot branch) in between
+    zero_copy: bool,
+    // Region crop stage; None on the zero-copy path
+    crop: Option<RegionCrop>,
+    // Live output branch off the tee, while streaming
+    live: Option<LiveBranch>,
+    fps: u32,
+    // Fed by the recording watcher at EOS or on error; taken by the first stop
+    done_rx: Option<Receiver<Result<RecordingResult>>>,
+}
+
+impl ActivePipeline {
+    // Failed pipelines are already torn down and hold no encoder session
+    fn is_running(&self) -> bool {
+        self.state != RecordingState::Failed
+    }
+
+    fn paused_ms(&self, now: i64) -> u64 {
+        let current = self.paused_at.map(|at| (now - at).max(0) as u64).unwrap_or(0);
+        self.paused_total_ms + current
+    }
+}
+
 struct PipelineManager {
-    pipelines: HashMap<String, Pipeline>,
-    active_gpu_streams: u32,
-    max_gpu_streams: u32, 
+    pipelines: HashMap<String, ActivePipeline>,
+    max_gpu_streams: u32,
+    gpu_policy: GpuBudgetPolicy,
+    event_sinks: Vec<StreamSink<RecordingEvent>>,
 }
 
 impl PipelineManager {
     fn new() -> Self {
         Self {
             pipelines: HashMap::new(),
-            active_gpu_streams: 0,
-            max_gpu_streams: 4, 
+            max_gpu_streams: 4,
+            gpu_policy: GpuBudgetPolicy::FallbackToSoftware,
+            event_sinks: Vec::new(),
         }
     }
+
+    // Derived from the records so it can't drift from what is actually running
+    fn gpu_streams(&self) -> u32 {
+        self.pipelines.values().filter(|p| p.uses_gpu && p.is_running()).count() as u32
+    }
+
+    fn cpu_streams(&self) -> u32 {
+        self.pipelines.values().filter(|p| !p.uses_gpu && p.is_running()).count() as u32
+    }
+
+    // Subscribers whose Dart stream was closed are dropped here
+    fn emit(&mut self, id: &str, kind: RecordingEventKind, state: RecordingState, message: Option<String>) {
+        let event = RecordingEvent {
+            id: id.to_string(),
+            kind,
+            state,
+            message,
+            timestamp: chrono::Utc::now().timestamp_millis(),
+        };
+        self.event_sinks.retain(|sink| sink.add(event.clone()).is_ok());
+    }
+
+    fn set_state(&mut self, id: &str, state: RecordingState, message: Option<String>) {
+        if let Some(active) = self.pipelines.get_mut(id) {
+            active.state = state;
+        }
+        self.emit(id, RecordingEventKind::StateChanged, state, message);
+    }
+
+    // The entry for `id` if it is still `pipeline`, not a newer one reusing the id
+    fn entry_for(&mut self, id: &str, pipeline: &Pipeline) -> Option<&mut ActivePipeline> {
+        self.pipelines.get_mut(id).filter(|active| &active.pipeline == pipeline)
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-enum EncoderQuality {
-    HardwareAV1,
-    HardwareHEVC,
-    HardwareH264,
-    SoftwareVP9,
-    SoftwareVP8,
-    SoftwareH264,
-    SoftwareFallback, // MJPEG
+pub struct PipelineResource {
+    pub id: String,
+    pub encoder: String,
+    pub uses_gpu: bool,
 }
 
-impl EncoderQuality {
-    fn to_gst_element_name(&self) -> &'static str {
-        match self {
-            EncoderQuality::HardwareAV1 => "vtenc_av1", 
-            EncoderQuality::HardwareHEVC => "vtenc_hevc", 
-            EncoderQuality::HardwareH264 => "vtenc_h264",
-            EncoderQuality::SoftwareVP9 => "vp9enc",
-            EncoderQuality::SoftwareVP8 => "vp8enc",
-            EncoderQuality::SoftwareH264 => "x264enc",
-            EncoderQuality::SoftwareFallback => "jpegenc",
-        }
+pub struct MediaResourceUsage {
+    pub gpu_streams: u32,
+    pub max_gpu_streams: u32,
+    pub cpu_streams: u32,
+    pub gpu_policy: GpuBudgetPolicy,
+    pub pipelines: Vec<PipelineResource>,
+}
+
+// Hardware encoders have a fixed number of sessions (consumer NVENC allows a
+// handful); 0 keeps every recording on software encoders.
+pub fn set_gpu_budget(max_gpu_streams: u32, policy: GpuBudgetPolicy) -> Result<()> {
+    let mut manager = PIPELINE_MANAGER.lock().map_err(|e| anyhow!("Lock error: {}", e))?;
+    manager.max_gpu_streams = max_gpu_streams;
+    manager.gpu_policy = policy;
+    Ok(())
+}
+
+pub fn get_media_resource_usage() -> MediaResourceUsage {
+    let manager = PIPELINE_MANAGER.lock().unwrap();
+    let mut pipelines: Vec<PipelineResource> = manager.pipelines.iter()
+        .map(|(id, p)| PipelineResource {
+            id: id.clone(),
+            encoder: p.encoder.to_string(),
+            uses_gpu: p.uses_gpu,
+        })
+        .collect();
+    pipelines.sort_by(|a, b| a.id.cmp(&b.id));
+    let gpu_streams = manager.gpu_streams();
+    MediaResourceUsage {
+        gpu_streams,
+        max_gpu_streams: manager.max_gpu_streams,
+        cpu_streams: manager.cpu_streams(),
+        gpu_policy: manager.gpu_policy,
+        pipelines,
     }
 }
 
@@ -61,14 +225,149 @@ pub fn init_gstreamer() -> Result<String> {
     }
 }
 
-fn find_best_encoder() -> EncoderQuality {
-    if ElementFactory::find("vtenc_av1").is_some() { return EncoderQuality::HardwareAV1; }
-    if ElementFactory::find("vtenc_hevc").is_some() { return EncoderQuality::HardwareHEVC; }
-    if ElementFactory::find("vtenc_h264").is_some() { return EncoderQuality::HardwareH264; }
-    if ElementFactory::find("vp9enc").is_some() { return EncoderQuality::SoftwareVP9; }
-    if ElementFactory::find("vp8enc").is_some() { return EncoderQuality::SoftwareVP8; }
-    if ElementFactory::find("x264enc").is_some() { return EncoderQuality::SoftwareH264; }
-    EncoderQuality::SoftwareFallback
+// Where recorded frames come from
+#[derive(Debug, Clone, PartialEq)]
+pub enum CaptureSource {
+    // Platform screen grabber: osxscreencapture (macOS), ximagesrc (X11),
+    // d3d11screencapturesrc (Windows)
+    Screen,
+    // PipeWire node handed out by an xdg-desktop-portal ScreenCast session (Wayland)
+    PipeWire { node_id: u32 },
+    // Synthetic live frames, so the full recording path runs headlessly in CI
+    Test,
+    // Webcam on its own, without the screen
+    Camera { input: CameraInput },
+    // One monitor; `DisplayDescriptor::id` from `list_displays`
+    Display { id: u32 },
+    // One application window, by `WindowDescriptor::id` from `list_windows` or
+    // by a case-insensitive title substring
+    Window { id: Option<u32>, title: Option<String> },
+}
+
+fn make(factory: &str) -> Result<Element> {
+    ElementFactory::make(factory).build()
+        .map_err(|_| anyhow!("GStreamer element {} is not available", factory))
+}
+
+// Adds `elements` to `pipeline` and links them in order
+fn add_chain(pipeline: &Pipeline, elements: &[&Element]) -> Result<()> {
+    pipeline.add_many(elements.iter().copied())?;
+    Element::link_many(elements.iter().copied())
+        .map_err(|_| anyhow!("Failed to link {}", elements.iter().map(|e| e.name().to_string()).collect::<Vec<_>>().join(" ! ")))?;
+    Ok(())
+}
+
+// Adds the elements producing raw video from `source` and returns the last one,
+// ready for an encoder. The macOS grabber stays in CVPixelBuffer memory when
+// `zero_copy` (VideoToolbox encoder), already at `fps`; every other case is
+// converted to system memory so any encoder can consume it.
+fn add_source(pipeline: &Pipeline, source: &CaptureSource, zero_copy: bool, cursor: bool, fps: u32) -> Result<Element> {
+    match source {
+        CaptureSource::Camera { input } => add_camera(pipeline, input),
+        CaptureSource::Test => {
+            let src = ElementFactory::make("videotestsrc")
+                .property("is-live", true)
+                .property_from_str("pattern", "smpte")
+                .build()
+                .map_err(|_| anyhow!("GStreamer element videotestsrc is not available"))?;
+            let caps = Caps::builder("video/x-raw").field("width", 1280i32).field("height", 720i32).build();
+            let filter = ElementFactory::make("capsfilter").property("caps", &caps).build()?;
+            let convert = make("videoconvert")?;
+            add_chain(pipeline, &[&src, &filter, &convert])?;
+            Ok(convert)
+        }
+        CaptureSource::PipeWire { node_id } => {
+            let src = make("pipewiresrc")?;
+            src.set_property("path", node_id.to_string());
+            src.set_property("do-timestamp", true);
+            let convert = make("videoconvert")?;
+            add_chain(pipeline, &[&src, &convert])?;
+            Ok(convert)
+        }
+        CaptureSource::Screen => add_screen_source(pipeline, zero_copy, cursor, fps, &|_| Ok(())),
+        CaptureSource::Display { id } => {
+            let displays = list_displays()?;
+            let (index, display) = displays.iter().enumerate()
+                .find(|(_, display)| display.id == *id)
+                .ok_or_else(|| anyhow!("Display {} not found", id))?;
+            add_screen_source(pipeline, zero_copy, cursor, fps, &|src| {
+                if cfg!(target_os = "windows") {
+                    src.set_property("monitor-index", index as i32);
+                } else if cfg!(target_os = "linux") {
+                    // ximagesrc grabs the whole X screen; limit it to this monitor (inclusive end)
+                    let scale = display.scale_factor as f64;
+                    let start_x = (display.x as f64 * scale).max(0.0) as u32;
+                    let start_y = (display.y as f64 * scale).max(0.0) as u32;
+                    src.set_property("startx", start_x);
+                    src.set_property("starty", start_y);
+                    src.set_property("endx", start_x + (display.width as f64 * scale) as u32 - 1);
+                    src.set_property("endy", start_y + (display.height as f64 * scale) as u32 - 1);
+                } else if !try_set_property(src, "display-id", &id.to_string()) {
+                    return Err(anyhow!("This screen capture plugin can't select display {}", id));
+                }
+                Ok(())
+            })
+        }
+        CaptureSource::Window { id, title } => {
+            let window = find_window(*id, title.as_deref())?;
+            add_screen_source(pipeline, zero_copy, cursor, fps, &|src| {
+                if cfg!(target_os = "windows") {
+                    // Window capture needs the Windows.Graphics.Capture backend
+                    try_set_property(src, "capture-api", "wgc");
+                    src.set_property("window-handle", window.id as u64);
+                } else if cfg!(target_os = "linux") {
+                    src.set_property("xid", window.id as u64);
+                }
+                // macOS captures the display and start_recording crops to the window
+                Ok(())
+            })
+        }
+    }
+}
+
+// Adds this platform's screen grabber, configured by `configure` (display or
+// window selection), and returns the last element like `add_source`.
+fn add_screen_source(
+    pipeline: &Pipeline,
+    zero_copy: bool,
+    cursor: bool,
+    fps: u32,
+    configure: &dyn Fn(&Element) -> Result<()>,
+) -> Result<Element> {
+    if cfg!(target_os = "macos") {
+        let src = make("osxscreencapture")?;
+        src.set_property("capture-cursor", cursor);
+        configure(&src)?;
+        let tail = if zero_copy {
+            let caps: Caps = format!("{},framerate={}/1", get_platform_zero_copy_caps(), fps).parse()
+                .map_err(|_| anyhow!("Invalid zero-copy caps"))?;
+            ElementFactory::make("capsfilter").property("caps", &caps).build()?
+        } else {
+            make("videoconvert")?
+        };
+        add_chain(pipeline, &[&src, &tail])?;
+        Ok(tail)
+    } else if cfg!(target_os = "windows") {
+        let src = make("d3d11screencapturesrc")?;
+        src.set_property("show-cursor", cursor);
+        configure(&src)?;
+        let download = make("d3d11download")?;
+        let convert = make("videoconvert")?;
+        add_chain(pipeline, &[&src, &download, &convert])?;
+        Ok(convert)
+    } else if cfg!(target_os = "linux") {
+        // Under Wayland ximagesrc only sees XWayland windows; callers should
+        // obtain a portal node and use CaptureSource::PipeWire instead.
+        let src = make("ximagesrc")?;
+        src.set_property("use-damage", false);
+        src.set_property("show-pointer", cursor);
+        configure(&src)?;
+        let convert = make("videoconvert")?;
+        add_chain(pipeline, &[&src, &convert])?;
+        Ok(convert)
+    } else {
+        Err(anyhow!("Screen capture is not supported on this platform"))
+    }
 }
 
 // OPTIMIZATION: Zero-Copy Caps
@@ -82,152 +381,856 @@ fn get_platform_zero_copy_caps() -> String {
     }
 }
 
+// Records the screen with the default config. WORKAHUB_TEST_CAPTURE=1 swaps
+// in the test source, so app-level tests can drive the normal recording API on
+// machines without a display.
 pub fn start_screen_recording(id: String, sink_path: String) -> Result<String> {
+    let source = if std::env::var("WORKAHUB_TEST_CAPTURE").is_ok_and(|v| v == "1") {
+        CaptureSource::Test
+    } else {
+        CaptureSource::Screen
+    };
+    start_recording(id, sink_path, RecordingConfig { source, ..RecordingConfig::default() })
+}
+
+// "dir/rec.mp4" -> "dir/rec_%05d.mp4", the printf-style pattern splitmuxsink
+// fills with the segment index. Literal '%' is escaped so it isn't taken as a
+// format directive.
+fn segment_pattern(sink_path: &str, extension: &str) -> String {
+    let path = Path::new(sink_path);
+    let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_else(|| "recording".to_string());
+    let file_name = format!("{}_%05d.{}", stem.replace('%', "%%"), extension);
+    match path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        Some(parent) => format!("{}/{}", parent.to_string_lossy().replace('%', "%%"), file_name),
+        None => file_name,
+    }
+}
+
+// Outcome of a recording once its last segment has been finalized
+#[derive(Debug, Clone)]
+pub struct RecordingResult {
+    pub id: String,
+    // Last segment written; None if no segment was ever closed
+    pub final_path: Option<String>,
+    pub segments: Vec<String>,
+    pub duration_ms: u64,
+    pub size_bytes: u64,
+}
+
+// Sole reader of a recording's bus. Tracks the pipeline's state, warnings and
+// errors in PIPELINE_MANAGER and forwards them as events; registers each
+// segment splitmuxsink closes as an artifact and queues it for upload while the
+// recording continues; and reports the finished recording on `done_tx` at EOS
+// (or the error that ended it). Runs until then or until the pipeline is dropped.
+fn spawn_recording_watcher(
+    id: String,
+    pipeline: &Pipeline,
+    encoder: &'static str,
+    done_tx: Sender<Result<RecordingResult>>,
+) -> Result<()> {
+    let bus = pipeline.bus().ok_or_else(|| anyhow!("Pipeline has no bus"))?;
+    let weak = pipeline.downgrade();
+    thread::spawn(move || {
+        let mut result = RecordingResult {
+            id: id.clone(),
+            final_path: None,
+            segments: Vec::new(),
+            duration_ms: 0,
+            size_bytes: 0,
+        };
+        let types = [
+            MessageType::Element,
+            MessageType::StateChanged,
+            MessageType::Warning,
+            MessageType::Error,
+            MessageType::Eos,
+        ];
+        while let Some(pipeline) = weak.upgrade() {
+            let Some(msg) = bus.timed_pop_filtered(ClockTime::from_mseconds(500), &types) else {
+                continue;
+            };
+            match msg.view() {
+                MessageView::Eos(..) => {
+                    let _ = done_tx.send(Ok(result));
+                    return;
+                }
+                // A failing live endpoint only ends the stream: errorignore at the
+                // head of the live branch keeps its flow errors off the tee
+                MessageView::Error(err) if msg.src().is_some_and(is_live_stream_element) => {
+                    let error = format!("{} ({})", err.error(), err.debug().map(|d| d.to_string()).unwrap_or_default());
+                    println!("Live stream of recording {} failed: {}", id, error);
+                    end_live_stream(&id, &pipeline, Some(error));
+                }
+                MessageView::Error(err) => {
+                    let error = format!("{} ({})", err.error(), err.debug().map(|d| d.to_string()).unwrap_or_default());
+                    println!("Recording {} failed: {}", id, error);
+                    // Tear down now so a dead pipeline doesn't keep its encoder session
+                    let _ = pipeline.set_state(State::Null);
+                    if let Ok(mut manager) = PIPELINE_MANAGER.lock() {
+                        if let Some(active) = manager.entry_for(&id, &pipeline) {
+                            active.state = RecordingState::Failed;
+                            active.last_error = Some(error.clone());
+                            manager.emit(&id, RecordingEventKind::Error, RecordingState::Failed, Some(error.clone()));
+                        }
+                    }
+                    let _ = done_tx.send(Err(anyhow!("Recording {} failed: {}", id, error)));
+                    return;
+                }
+                MessageView::Warning(warning) => {
+                    let text = warning.error().to_string();
+                    if let Ok(mut manager) = PIPELINE_MANAGER.lock() {
+                        if let Some(active) = manager.entry_for(&id, &pipeline) {
+                            active.warning_count += 1;
+                            let state = active.state;
+                            manager.emit(&id, RecordingEventKind::Warning, state, Some(text));
+                        }
+                    }
+                }
+                // Only the pipeline's own transitions; every element posts these
+                MessageView::StateChanged(change)
+                    if change.current() == State::Playing
+                        && msg.src().is_some_and(|src| src == pipeline.upcast_ref::<gstreamer::Object>()) =>
+                {
+                    if let Ok(mut manager) = PIPELINE_MANAGER.lock() {
+                        if manager.entry_for(&id, &pipeline).is_some_and(|a| a.state == RecordingState::Starting) {
+                            manager.set_state(&id, RecordingState::Recording, None);
+                        }
+                    }
+                }
+                MessageView::Element(..) => {
+                    if let Some(location) = segment_closed(&msg, &mut result) {
+                        register_segment(&id, encoder, &location, &mut result);
+                        if let Ok(mut manager) = PIPELINE_MANAGER.lock() {
+                            if let Some(active) = manager.entry_for(&id, &pipeline) {
+                                let state = active.state;
+                                manager.emit(&id, RecordingEventKind::SegmentClosed, state, Some(location));
+                            }
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+    });
+    Ok(())
+}
+
+// Location of the segment if `msg` is splitmuxsink closing one; running time
+// at close is the recording length so far.
+fn segment_closed(msg: &gstreamer::Message, result: &mut RecordingResult) -> Option<String> {
+    let s = msg.structure().filter(|s| s.has_name("splitmuxsink-fragment-closed"))?;
+    let location = s.get::<String>("location").ok()?;
+    if let Ok(ns) = s.get::<u64>("running-time") {
+        result.duration_ms = ns / 1_000_000;
+    }
+    Some(location)
+}
+
+fn register_segment(id: &str, encoder: &str, location: &str, result: &mut RecordingResult) {
+    let metadata = json!({
+        "recording_id": id,
+        "segment_index": result.segments.len(),
+        "running_time_ms": result.duration_ms,
+        "encoder": encoder,
+    });
+    match register_artifact("recording_segment", location.to_string(), Some(metadata.to_string())) {
+        Ok(artifact) => {
+            result.size_bytes += artifact.size;
+            if let Err(e) = enqueue_upload(artifact.id) {
+                println!("Failed to queue segment {}: {}", location, e);
+            }
+        }
+        Err(e) => println!("Failed to register segment {}: {}", location, e),
+    }
+    result.segments.push(location.to_string());
+    result.final_path = Some(location.to_string());
+}
+
+// Picks the encoder for a new recording under the GPU budget, among those
+// `accept` allows (codecs the container can carry). Called with the manager
+// locked so admission and registration happen atomically.
+fn admit_encoder(
+    manager: &PipelineManager,
+    id: &str,
+    accept: &dyn Fn(&EncoderSpec) -> bool,
+) -> Result<(&'static EncoderSpec, String)> {
+    let gpu_streams = manager.gpu_streams();
+    if gpu_streams < manager.max_gpu_streams {
+        return select_encoder(accept);
+    }
+
+    match manager.gpu_policy {
+        GpuBudgetPolicy::FallbackToSoftware => {
+            let (spec, reason) = select_encoder(&|spec| accept(spec) && !spec.is_hardware())?;
+            Ok((spec, format!("GPU budget full ({}/{}), using software: {}", gpu_streams, manager.max_gpu_streams, reason)))
+        }
+        GpuBudgetPolicy::Reject => {
+            let (spec, reason) = select_encoder(accept)?;
+            if spec.is_hardware() {
+                return Err(anyhow!(
+                    "GPU budget full ({}/{}), recording {} rejected",
+                    gpu_streams, manager.max_gpu_streams, id
+                ));
+            }
+            Ok((spec, reason))
+        }
+    }
+}
+
+// Output is split into `config.segment_secs` long files next to `sink_path`
+// ("rec.mp4" -> "rec_00000.mp4", ...), so a crash loses at most the open
+// segment and finished ones can upload while recording continues.
+pub fn start_recording(id: String, sink_path: String, config: RecordingConfig) -> Result<String> {
+    config.validate()?;
+
+    // Warm the probe cache before taking the lock; the first probe runs test encodes
+    probe_encoders(false)?;
+
     let mut manager = PIPELINE_MANAGER.lock().map_err(|e| anyhow!("Lock error: {}", e))?;
 
     if manager.pipelines.contains_key(&id) {
         return Err(anyhow!("Pipeline with ID {} already exists", id));
     }
 
-    let best_encoder = find_best_encoder();
-    let use_gpu = matches!(best_encoder, EncoderQuality::HardwareAV1 | EncoderQuality::HardwareHEVC | EncoderQuality::HardwareH264);
+    let container = config.container;
+    let (encoder, encoder_reason) = admit_encoder(&manager, &id, &|spec| container.accepts(spec.codec))?;
+    let use_gpu = encoder.is_hardware();
 
-    if use_gpu && manager.active_gpu_streams >= manager.max_gpu_streams {
-        println!("Warning: High GPU load ({}/{})", manager.active_gpu_streams, manager.max_gpu_streams);
+    // osxscreencapture only grabs whole displays, so a window is recorded as its
+    // rectangle of the main display
+    let mut region = config.region;
+    if cfg!(target_os = "macos") {
+        if let CaptureSource::Window { id: window_id, title } = &config.source {
+            let window = find_window(*window_id, title.as_deref())?;
+            let displays = list_displays()?;
+            let display = displays.iter().find(|d| d.is_primary)
+                .ok_or_else(|| anyhow!("No primary display"))?;
+            let bounds = region_on_display(&window, display)?;
+            // A configured region is relative to the window
+            region = Some(match region {
+                Some(r) => RecordingRegion { x: bounds.x + r.x, y: bounds.y + r.y, width: r.width, height: r.height },
+                None => bounds,
+            });
+        }
     }
 
-    let encoder_name = match best_encoder {
-        EncoderQuality::HardwareHEVC => "vtenc_hevc", 
-        _ => best_encoder.to_gst_element_name(),
-    };
+    // Only the macOS screen grabber produces CVPixelBuffers. compositor,
+    // videocrop and the live branch's videoconvert work on system memory, so
+    // PiP, regions and live streaming give up the zero-copy path.
+    let zero_copy = encoder.vendor == EncoderVendor::VideoToolbox
+        && matches!(config.source, CaptureSource::Screen | CaptureSource::Display { .. })
+        && config.pip.is_none()
+        && region.is_none()
+        && config.live.is_none();
+    let pipeline = Pipeline::with_name(&format!("recording-{}", id));
+
+    // 1. source: screen grabber for this platform, a camera, or videotestsrc in test
+    //    mode; on macOS ENFORCE CVPixelBuffer to prevent silent software
+    //    fallback/copy, elsewhere convert to system memory
+    let mut tail = add_source(&pipeline, &config.source, zero_copy, config.capture_cursor, config.fps)?;
+
+    // 2. crop to the region before the camera goes on top. The crop stage is
+    //    there even without a region so one can be set while recording.
+    let mut crop = None;
+    if !zero_copy {
+        let (region_crop, crop_tail) = add_region_crop(&pipeline, &tail, region)?;
+        tail = crop_tail;
+        crop = Some(region_crop);
+    }
 
-    let zero_copy_caps = get_platform_zero_copy_caps();
-
-    // OPTIMIZED PIPELINE:
-    // 1. osxscreencapture: Captures screen (outputs CVPixelBuffers on macOS)
-    // 2. capsfilter: ENFORCE CVPixelBuffer to prevent silent software fallback/copy
-    // 3. tee: Allows us to branch the stream (e.g. for live preview/analysis) without stopping
-    // 4. queue: Decouples encoder thread
-    // 5. encoder: Hardware encoder (reads CVPixelBuffer directly)
-    // 6. mux -> file
-    let pipeline_str = format!(
-        "osxscreencapture capture-cursor=true ! {caps} ! tee name=t \
-         t. ! queue max-size-buffers=1 ! {encoder} bitrate=4000 ! mp4mux ! filesink location={sink} \
-         t. ! queue leaky=downstream ! appsink name=snapshot_sink drop=true max-buffers=1 emit-signals=true",
-        caps = zero_copy_caps,
-        encoder = encoder_name,
-        sink = sink_path
-    );
-
-    let pipeline = gstreamer::parse::launch(&pipeline_str)
-        .map_err(|e| anyhow!("Failed to parse pipeline: {}", e))?;
-
-    let pipeline = pipeline.dynamic_cast::<Pipeline>()
-        .map_err(|_| anyhow!("Cast to pipeline failed"))?;
+    // 3. with PiP the camera is composited over the screen
+    let mut pip_mixer = None;
+    if config.pip.is_some() {
+        let (compositor, mixer_tail) = add_pip_mixer(&pipeline, &tail)?;
+        tail = mixer_tail;
+        pip_mixer = Some(compositor);
+    }
+
+    // rate-limit to the configured fps (the zero-copy caps filter already
+    // carries the framerate)
+    if !zero_copy {
+        let rate = make("videorate")?;
+        let caps = Caps::builder("video/x-raw").field("framerate", gstreamer::Fraction::new(config.fps as i32, 1)).build();
+        let filter = ElementFactory::make("capsfilter").property("caps", &caps).build()?;
+        add_chain(&pipeline, &[&rate, &filter])?;
+        tail.link(&rate)?;
+        tail = filter;
+    }
+
+    // 4. tee: Allows us to branch the stream (e.g. for live preview/analysis) without stopping.
+    //    Branches can come and go while recording, so a moment with a pad
+    //    unlinked must not stop the stream.
+    let tee = ElementFactory::make("tee").name("t").property("allow-not-linked", true).build()?;
+    pipeline.add(&tee)?;
+    tail.link(&tee)?;
+
+    // 5. queue: Decouples encoder thread
+    // 6. encoder: best probed encoder the container can carry (hardware reads
+    //    CVPixelBuffer/GPU memory directly)
+    // 7. parse -> splitmuxsink: a finalized file every `segment_secs`, split on
+    //    keyframes it requests from the encoder. Paths are plain string
+    //    properties, so spaces or '!' in them need no quoting.
+    let record_queue = ElementFactory::make("queue").property("max-size-buffers", 1u32).build()?;
+    let encoder_elem = ElementFactory::make(encoder.factory).name("video_encoder").build()
+        .map_err(|_| anyhow!("GStreamer element {} is not available", encoder.factory))?;
+    let muxer = ElementFactory::make("splitmuxsink")
+        .name("segment_sink")
+        .property("muxer-factory", container.muxer())
+        .property("send-keyframe-requests", true)
+        .property("max-size-time", ClockTime::from_seconds(config.segment_secs as u64).nseconds())
+        .property("location", segment_pattern(&sink_path, container.extension()))
+        .build()
+        .map_err(|_| anyhow!("GStreamer element splitmuxsink is not available"))?;
+    let mut record_branch = vec![&record_queue, &encoder_elem];
+    let parser = encoder.parser.map(make).transpose()?;
+    if let Some(parser) = &parser {
+        record_branch.push(parser);
+    }
+    record_branch.push(&muxer);
+    add_chain(&pipeline, &record_branch)?;
+    tee.link(&record_queue)?;
+
+    // Property names and units differ per encoder, so they are set from its spec
+    let bitrate_kbps = config.bitrate_kbps();
+    apply_encoder_properties(&encoder_elem, encoder, bitrate_kbps, config.keyframe_interval_frames());
 
+    // 8. snapshot branch for capture_live_snapshot. The valve stays shut until a
+    //    snapshot is requested, so frames are only converted to RGBA on demand.
+    //    videoconvert can't read CVPixelBuffer memory, so the zero-copy path has
+    //    no snapshots.
+    if !zero_copy {
+        let snapshot_queue = ElementFactory::make("queue")
+            .property_from_str("leaky", "downstream")
+            .property("max-size-buffers", 1u32)
+            .build()?;
+        // Sticky events still pass the shut valve: caps, and the EOS that
+        // stop_pipeline waits for at every sink
+        let snapshot_valve = ElementFactory::make("valve")
+            .name("snapshot_valve")
+            .property("drop", true)
+            .property_from_str("drop-mode", "forward-sticky-events")
+            .build()?;
+        let snapshot_convert = make("videoconvert")?;
+        let snapshot_sink = AppSink::builder()
+            .name("snapshot_sink")
+            .caps(&Caps::builder("video/x-raw").field("format", "RGBA").build())
+            .drop(true)
+            .max_buffers(1)
+            .sync(false)
+            .build();
+        // Never prerolls while the valve is shut, so it mustn't hold up state changes
+        snapshot_sink.set_property("async", false);
+        add_chain(&pipeline, &[&snapshot_queue, &snapshot_valve, &snapshot_convert, snapshot_sink.upcast_ref::<Element>()])?;
+        tee.link(&snapshot_queue)?;
+    }
+
+    add_audio_tracks(&pipeline, &muxer, &config.audio, container.audio_codec())?;
+
+    let pip = match (&config.pip, &pip_mixer) {
+        (Some(pip), Some(compositor)) => Some(attach_pip(&pipeline, compositor, pip)?),
+        _ => None,
+    };
+    // 9. optional live branch, also addable later with start_live_stream
+    let live = config.live.as_ref()
+        .map(|options| attach_live_stream(&pipeline, &tee, options, config.fps))
+        .transpose()?;
+
+    let (done_tx, done_rx) = mpsc::channel();
+    spawn_recording_watcher(id.clone(), &pipeline, encoder.factory, done_tx)?;
     pipeline.set_state(State::Playing)
         .map_err(|e| anyhow!("Failed to set state: {}", e))?;
 
-    if use_gpu {
-        manager.active_gpu_streams += 1;
+    manager.pipelines.insert(id.clone(), ActivePipeline {
+        pipeline,
+        encoder: encoder.factory,
+        uses_gpu: use_gpu,
+        state: RecordingState::Starting,
+        started_at: chrono::Utc::now().timestamp_millis(),
+        paused_at: None,
+        paused_total_ms: 0,
+        last_error: None,
+        warning_count: 0,
+        pip,
+        zero_copy,
+        crop,
+        live,
+        fps: config.fps,
+        done_rx: Some(done_rx),
+    });
+    manager.emit(&id, RecordingEventKind::StateChanged, RecordingState::Starting, Some(encoder_reason.clone()));
+    if let Some(url) = config.live.as_ref().map(|live| live.url.clone()) {
+        manager.emit(&id, RecordingEventKind::LiveStreamStarted, RecordingState::Starting, Some(url));
     }
-    manager.pipelines.insert(id.clone(), pipeline);
 
-    Ok(format!("Started recording {} with {}", id, encoder_name))
+    Ok(format!("Started recording {} with {} at {} kbps ({})", id, encoder.factory, bitrate_kbps, encoder_reason))
 }
 
-// Generate a thumbnail from a VIDEO FILE using Hardware Decoding
-// Uses `uridecodebin` which automatically selects hardware decoders (vtdec)
+// Writes a JPEG (height 360) of the frame nearest `position_ms` to
+// `output_path`. `render_video_thumbnail` returns the bytes instead and can
+// seek to the exact frame.
 pub fn generate_video_thumbnail(video_path: String, output_path: String, position_ms: i64) -> Result<String> {
-    // Pipeline:
-    // filesrc -> parse -> HW decode -> scale -> convert -> jpegenc -> file
-    // We use `videoscale` to ensure the thumbnail is reasonable size (e.g. height 360)
-    let pipeline_str = format!(
-        "filesrc location={} ! decodebin ! videoconvert ! videoscale ! video/x-raw,height=360 ! jpegenc ! filesink location={}",
-        video_path, output_path
-    );
-
-    let pipeline = gstreamer::parse::launch(&pipeline_str)
-        .map_err(|e| anyhow!("Failed to parse thumbnail pipeline: {}", e))?;
-
-    let pipeline = pipeline.dynamic_cast::<Pipeline>()
-        .map_err(|_| anyhow!("Cast to pipeline failed"))?;
-
-    // Seek to position
-    pipeline.set_state(State::Paused)?;
-    
-    // Simple seek (this is blocking/synchronous for simplicity in this snippet, 
-    // real app might want async waiting for Preroll)
-    let position = gstreamer::ClockTime::from_mseconds(position_ms as u64);
-    pipeline.seek_simple(gstreamer::SeekFlags::FLUSH | gstreamer::SeekFlags::KEY_UNIT, position)?;
-
-    // Play to process the frame
-    pipeline.set_state(State::Playing)?;
-
-    // Wait for EOS or Error (short timeout)
-    let bus = pipeline.bus().unwrap();
-    for msg in bus.iter_timed(gstreamer::ClockTime::from_seconds(5)) {
-        use gstreamer::MessageView;
-        match msg.view() {
-            MessageView::Eos(..) => break,
-            MessageView::Error(err) => {
-                pipeline.set_state(State::Null)?;
-                return Err(anyhow!("Thumbnail error: {}", err.error()));
+    let options = VideoThumbnailOptions {
+        position_ms: position_ms.max(0) as u64,
+        ..VideoThumbnailOptions::default()
+    };
+    let thumbnail = render_video_thumbnail(video_path, options)?;
+    std::fs::write(&output_path, &thumbnail.bytes)
+        .map_err(|e| anyhow!("Failed to write thumbnail {}: {}", output_path, e))?;
+    Ok("Thumbnail generated".to_string())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SnapshotFormat {
+    // Tightly packed RGBA8 rows, ready for `decodeImageFromPixels`
+    Rgba,
+    Jpeg,
+}
+
+pub struct LiveSnapshot {
+    pub width: u32,
+    pub height: u32,
+    // Bytes per row of `bytes` for Rgba (always width * 4); 0 for Jpeg
+    pub stride: u32,
+    pub format: SnapshotFormat,
+    // Recording position of the frame (buffer PTS), if timestamped
+    pub position_ms: Option<u64>,
+    // Unix epoch milliseconds when the frame was pulled
+    pub timestamp: i64,
+    pub bytes: Vec<u8>,
+}
+
+const SNAPSHOT_JPEG_QUALITY: u8 = 80;
+
+// Next frame from the recording's snapshot branch, which negotiates RGBA
+// caps. Opens the branch's valve for one frame and waits at most `timeout_ms`
+// so a stalled or paused pipeline can't block the caller.
+pub fn capture_live_snapshot(pipeline_id: String, format: SnapshotFormat, timeout_ms: u32) -> Result<LiveSnapshot> {
+    // Released before pulling so a slow frame doesn't hold up the manager
+    let (valve, appsink) = {
+        let manager = PIPELINE_MANAGER.lock().map_err(|e| anyhow!("Lock error: {}", e))?;
+        let active = manager.pipelines.get(&pipeline_id)
+            .ok_or_else(|| anyhow!("Pipeline not found"))?;
+        if active.zero_copy {
+            return Err(anyhow!("Recording {} uses the zero-copy path and has no live snapshots", pipeline_id));
+        }
+        let valve = active.pipeline.by_name("snapshot_valve")
+            .ok_or_else(|| anyhow!("Snapshot valve not found in pipeline"))?;
+        let appsink = active.pipeline.by_name("snapshot_sink")
+            .ok_or_else(|| anyhow!("Snapshot sink not found in pipeline"))?
+            .dynamic_cast::<AppSink>()
+            .map_err(|_| anyhow!("Sink cast failed"))?;
+        (valve, appsink)
+    };
+
+    // A frame left over from the last snapshot would be stale
+    let _ = appsink.try_pull_sample(ClockTime::ZERO);
+    valve.set_property("drop", false);
+    let sample = appsink.try_pull_sample(ClockTime::from_mseconds(timeout_ms as u64));
+    valve.set_property("drop", true);
+    let sample = sample.ok_or_else(|| anyhow!("No frame within {}ms", timeout_ms))?;
+    let (width, height, rgba) = rgba_from_sample(&sample)?;
+    let (bytes, stride) = match format {
+        SnapshotFormat::Rgba => (rgba, width * 4),
+        SnapshotFormat::Jpeg => (encode_jpeg(width, height, rgba, SNAPSHOT_JPEG_QUALITY)?, 0),
+    };
+
+    Ok(LiveSnapshot {
+        width,
+        height,
+        stride,
+        format,
+        position_ms: sample.buffer().and_then(|buffer| buffer.pts()).map(|pts| pts.mseconds()),
+        timestamp: chrono::Utc::now().timestamp_millis(),
+        bytes,
+    })
+}
+
+// Tightly packed pixels of an RGBA sample, as (width, height, bytes)
+pub(crate) fn rgba_from_sample(sample: &gstreamer::Sample) -> Result<(u32, u32, Vec<u8>)> {
+    let buffer = sample.buffer().ok_or_else(|| anyhow!("No buffer in sample"))?;
+    let caps = sample.caps().ok_or_else(|| anyhow!("Sample has no caps"))?;
+    let info = gstreamer_video::VideoInfo::from_caps(caps)
+        .map_err(|e| anyhow!("Unexpected frame caps: {}", e))?;
+    if info.format() != gstreamer_video::VideoFormat::Rgba {
+        return Err(anyhow!("Negotiated {:?}, expected RGBA", info.format()));
+    }
+
+    let frame = gstreamer_video::VideoFrameRef::from_buffer_ref_readable(buffer, &info)
+        .map_err(|_| anyhow!("Buffer map failed"))?;
+    let (width, height) = (info.width(), info.height());
+    let src_stride = frame.plane_stride()[0] as usize;
+    let row_len = width as usize * 4;
+    let data = frame.plane_data(0).map_err(|e| anyhow!("No RGBA plane: {}", e))?;
+
+    // Drop the row padding GStreamer may add for alignment
+    let mut rgba = Vec::with_capacity(row_len * height as usize);
+    for row in data.chunks(src_stride).take(height as usize) {
+        rgba.extend_from_slice(&row[..row_len]);
+    }
+    Ok((width, height, rgba))
+}
+
+pub(crate) fn encode_jpeg(width: u32, height: u32, rgba: Vec<u8>, quality: u8) -> Result<Vec<u8>> {
+    let image = image::RgbaImage::from_raw(width, height, rgba)
+        .ok_or_else(|| anyhow!("Frame size mismatch"))?;
+    let rgb = image::DynamicImage::ImageRgba8(image).to_rgb8();
+    let mut jpeg = Vec::new();
+    image::codecs::jpeg::JpegEncoder::new_with_quality(&mut jpeg, quality).encode_image(&rgb)?;
+    Ok(jpeg)
+}
+
+const DEFAULT_STOP_TIMEOUT_MS: u32 = 10_000;
+
+pub fn stop_pipeline(id: String) -> Result<RecordingResult> {
+    stop_pipeline_with_timeout(id, DEFAULT_STOP_TIMEOUT_MS)
+}
+
+// Sends EOS so splitmuxsink finalizes the open segment (writes its moov atom),
+// waits up to `timeout_ms` for it to reach the sinks, then tears down. Going
+// straight to Null would leave the last segment unplayable.
+pub fn stop_pipeline_with_timeout(id: String, timeout_ms: u32) -> Result<RecordingResult> {
+    // The lock isn't held while waiting so status queries and the watcher keep working
+    let (pipeline, done_rx, was_paused) = {
+        let mut manager = PIPELINE_MANAGER.lock().map_err(|e| anyhow!("Lock error: {}", e))?;
+        let active = manager.pipelines.get_mut(&id).ok_or_else(|| anyhow!("Pipeline {} not found", id))?;
+        let done_rx = active.done_rx.take().ok_or_else(|| anyhow!("Pipeline {} is already stopping", id))?;
+        let pipeline = active.pipeline.clone();
+        let was_paused = active.state == RecordingState::Paused;
+        if active.state != RecordingState::Failed {
+            manager.set_state(&id, RecordingState::Stopping, None);
+        }
+        (pipeline, done_rx, was_paused)
+    };
+    // An unreachable endpoint would otherwise hold up EOS and the last segment
+    end_live_stream(&id, &pipeline, None);
+
+    // Already ended (error or upstream EOS): nothing more will be written
+    let outcome = match done_rx.try_recv() {
+        Ok(outcome) => outcome,
+        Err(_) => {
+            // Queued before resuming so a paused recording gains no extra frames
+            pipeline.send_event(gstreamer::event::Eos::new());
+            if was_paused {
+                let _ = pipeline.set_state(State::Playing);
+            }
+            match done_rx.recv_timeout(Duration::from_millis(timeout_ms as u64)) {
+                Ok(outcome) => outcome,
+                Err(RecvTimeoutError::Timeout) => Err(anyhow!(
+                    "Recording {} did not finalize within {}ms; the last segment may be unplayable",
+                    id, timeout_ms
+                )),
+                Err(RecvTimeoutError::Disconnected) => Err(anyhow!("Recording {} watcher exited", id)),
             }
-            _ => (),
         }
+    };
+
+    let _ = pipeline.set_state(State::Null);
+
+    let mut manager = PIPELINE_MANAGER.lock().map_err(|e| anyhow!("Lock error: {}", e))?;
+    manager.pipelines.remove(&id);
+    match &outcome {
+        Ok(result) => manager.emit(&id, RecordingEventKind::StateChanged, RecordingState::Stopped, result.final_path.clone()),
+        Err(e) => manager.emit(&id, RecordingEventKind::StateChanged, RecordingState::Failed, Some(e.to_string())),
     }
-    
-    pipeline.set_state(State::Null)?;
-    Ok("Thumbnail generated".to_string())
+    outcome
 }
 
-// Capture a snapshot from an ACTIVE pipeline
-// This taps into the `appsink` named "snapshot_sink" we added to the recording pipeline
-pub fn capture_live_snapshot(pipeline_id: String) -> Result<Vec<u8>> {
-    let manager = PIPELINE_MANAGER.lock().unwrap();
-    let pipeline = manager.pipelines.get(&pipeline_id)
-        .ok_or_else(|| anyhow!("Pipeline not found"))?;
+// Same as `stop_pipeline_with_timeout` without blocking the Dart isolate's
+// worker while the encoder drains
+pub async fn stop_pipeline_async(id: String, timeout_ms: u32) -> Result<RecordingResult> {
+    tokio::task::spawn_blocking(move || stop_pipeline_with_timeout(id, timeout_ms))
+        .await
+        .map_err(|e| anyhow!("Stop task failed: {}", e))?
+}
 
-    let appsink_elem = pipeline.by_name("snapshot_sink")
-        .ok_or_else(|| anyhow!("Snapshot sink not found in pipeline"))?;
-    
-    let appsink = appsink_elem.dynamic_cast::<AppSink>()
-        .map_err(|_| anyhow!("Sink cast failed"))?;
+// Pauses capture and encoding without closing the current segment. On resume
+// live sources timestamp from the running time, which doesn't advance while
+// the pipeline is paused, so the recording plays back without a gap.
+pub fn pause_recording(id: String) -> Result<()> {
+    let mut manager = PIPELINE_MANAGER.lock().map_err(|e| anyhow!("Lock error: {}", e))?;
+    let active = manager.pipelines.get_mut(&id).ok_or_else(|| anyhow!("Pipeline {} not found", id))?;
+    if active.state != RecordingState::Recording {
+        return Err(anyhow!("Recording {} can't be paused while {:?}", id, active.state));
+    }
+    active.pipeline.set_state(State::Paused)
+        .map_err(|e| anyhow!("Failed to pause {}: {}", id, e))?;
+    active.paused_at = Some(chrono::Utc::now().timestamp_millis());
+    manager.set_state(&id, RecordingState::Paused, None);
+    Ok(())
+}
 
-    // Pull sample
-    let sample = appsink.pull_sample().map_err(|e| anyhow!("Failed to pull sample: {}", e))?;
-    let buffer = sample.buffer().ok_or_else(|| anyhow!("No buffer in sample"))?;
-    
-    // Map buffer and return bytes (JPEG encoding would ideally happen inside pipeline for performance,
-    // but here we just return raw or pre-encoded data depending on what we configured. 
-    // The current pipeline sends raw. Let's assume we want raw bytes for Flutter to render or we update pipeline to JPEG).
-    // For simplicity, let's update the pipeline above to `jpegenc` before appsink if we want JPEGs, 
-    // or just return raw RGBA. Flutter likes RGBA.
-    
-    // NOTE: This simple extraction gets the RAW buffer. 
-    // Real implementation would need to convert to suitable format (RGBA/PNG) if not done in pipeline.
-    // For now, returning raw bytes size.
-    
-    let map = buffer.map_readable().map_err(|_| anyhow!("Buffer map failed"))?;
-    Ok(map.as_slice().to_vec())
-}
-
-pub fn stop_pipeline(id: String) -> Result<String> {
+pub fn resume_recording(id: String) -> Result<()> {
+    let mut manager = PIPELINE_MANAGER.lock().map_err(|e| anyhow!("Lock error: {}", e))?;
+    let active = manager.pipelines.get_mut(&id).ok_or_else(|| anyhow!("Pipeline {} not found", id))?;
+    if active.state != RecordingState::Paused {
+        return Err(anyhow!("Recording {} is not paused", id));
+    }
+    active.pipeline.set_state(State::Playing)
+        .map_err(|e| anyhow!("Failed to resume {}: {}", id, e))?;
+    let now = chrono::Utc::now().timestamp_millis();
+    active.paused_total_ms = active.paused_ms(now);
+    active.paused_at = None;
+    manager.set_state(&id, RecordingState::Recording, None);
+    Ok(())
+}
+
+// Moves, resizes or hides the camera overlay of a running recording
+pub fn set_pip_layout(id: String, layout: PipLayout) -> Result<()> {
+    let manager = PIPELINE_MANAGER.lock().map_err(|e| anyhow!("Lock error: {}", e))?;
+    let active = manager.pipelines.get(&id).ok_or_else(|| anyhow!("Pipeline {} not found", id))?;
+    let pip = active.pip.as_ref().ok_or_else(|| anyhow!("Recording {} has no camera overlay", id))?;
+    pip.set_layout(layout);
+    Ok(())
+}
+
+// Track indexes follow `AudioOptions::tracks`, also when they are mixed
+pub fn set_audio_track_muted(id: String, track_index: u32, muted: bool) -> Result<()> {
+    let manager = PIPELINE_MANAGER.lock().map_err(|e| anyhow!("Lock error: {}", e))?;
+    let active = manager.pipelines.get(&id).ok_or_else(|| anyhow!("Pipeline {} not found", id))?;
+    let volume = active.pipeline.by_name(&volume_name(track_index as usize))
+        .ok_or_else(|| anyhow!("Recording {} has no audio track {}", id, track_index))?;
+    volume.set_property("mute", muted);
+    Ok(())
+}
+
+// Moves the recorded rectangle (source pixels) while recording; None records
+// the whole frame again. The output keeps the first region's size, so other
+// sizes are scaled into it.
+pub fn set_recording_region(id: String, region: Option<RecordingRegion>) -> Result<()> {
+    if let Some(region) = &region {
+        region.validate()?;
+    }
+    let manager = PIPELINE_MANAGER.lock().map_err(|e| anyhow!("Lock error: {}", e))?;
+    let active = manager.pipelines.get(&id).ok_or_else(|| anyhow!("Pipeline {} not found", id))?;
+    let crop = active.crop.as_ref()
+        .ok_or_else(|| anyhow!("Recording {} uses the zero-copy path and can't be cropped", id))?;
+    crop.set_region(region);
+    Ok(())
+}
+
+// Lifecycle events for every recording; multiple listeners may subscribe
+pub fn recording_events(sink: StreamSink<RecordingEvent>) -> Result<()> {
     let mut manager = PIPELINE_MANAGER.lock().map_err(|e| anyhow!("Lock error: {}", e))?;
+    manager.event_sinks.push(sink);
+    Ok(())
+}
 
-    if let Some(pipeline) = manager.pipelines.remove(&id) {
-        let _ = pipeline.set_state(State::Null);
-        if manager.active_gpu_streams > 0 {
-             manager.active_gpu_streams -= 1;
+// Starts streaming a running recording to `options.url` without interrupting
+// the file being written. One live stream per recording.
+pub fn start_live_stream(id: String, options: LiveStreamOptions) -> Result<()> {
+    let mut manager = PIPELINE_MANAGER.lock().map_err(|e| anyhow!("Lock error: {}", e))?;
+    let active = manager.pipelines.get_mut(&id).ok_or_else(|| anyhow!("Pipeline {} not found", id))?;
+    if !matches!(active.state, RecordingState::Starting | RecordingState::Recording | RecordingState::Paused) {
+        return Err(anyhow!("Recording {} can't stream while {:?}", id, active.state));
+    }
+    if let Some(live) = &active.live {
+        return Err(anyhow!("Recording {} is already streaming to {}", id, live.url));
+    }
+    if active.zero_copy {
+        return Err(anyhow!("Recording {} uses the zero-copy path and can't stream; start it with a live stream configured", id));
+    }
+    let tee = active.pipeline.by_name("t").ok_or_else(|| anyhow!("Recording {} has no tee", id))?;
+    active.live = Some(attach_live_stream(&active.pipeline, &tee, &options, active.fps)?);
+    let state = active.state;
+    manager.emit(&id, RecordingEventKind::LiveStreamStarted, state, Some(options.url));
+    Ok(())
+}
+
+// Stops streaming; the recording continues
+pub fn stop_live_stream(id: String) -> Result<()> {
+    let pipeline = {
+        let manager = PIPELINE_MANAGER.lock().map_err(|e| anyhow!("Lock error: {}", e))?;
+        let active = manager.pipelines.get(&id).ok_or_else(|| anyhow!("Pipeline {} not found", id))?;
+        if active.live.is_none() {
+            return Err(anyhow!("Recording {} is not streaming", id));
         }
-        Ok(format!("Stopped pipeline {}", id))
-    } else {
-        Err(anyhow!("Pipeline {} not found", id))
+        active.pipeline.clone()
+    };
+    end_live_stream(&id, &pipeline, None);
+    Ok(())
+}
+
+// Takes the live branch out of the entry and tears it down outside the lock,
+// since detaching waits for the tee. No-op if there is none.
+fn end_live_stream(id: &str, pipeline: &Pipeline, error: Option<String>) {
+    let Ok(mut manager) = PIPELINE_MANAGER.lock() else { return };
+    let Some(active) = manager.entry_for(id, pipeline) else { return };
+    let Some(live) = active.live.take() else { return };
+    let state = active.state;
+    drop(manager);
+
+    if let Some(tee) = pipeline.by_name("t") {
+        detach_live_stream(pipeline, &tee, live);
+    }
+    if let Ok(mut manager) = PIPELINE_MANAGER.lock() {
+        if let (Some(active), Some(error)) = (manager.entry_for(id, pipeline), &error) {
+            active.warning_count += 1;
+            active.last_error = Some(error.clone());
+        }
+        manager.emit(id, RecordingEventKind::LiveStreamStopped, state, error);
     }
 }
 
+pub fn get_recording_status(id: String) -> Result<RecordingStatus> {
+    let manager = PIPELINE_MANAGER.lock().map_err(|e| anyhow!("Lock error: {}", e))?;
+    let active = manager.pipelines.get(&id).ok_or_else(|| anyhow!("Pipeline {} not found", id))?;
+    Ok(RecordingStatus {
+        id,
+        state: active.state,
+        encoder: active.encoder.to_string(),
+        uses_gpu: active.uses_gpu,
+        started_at: active.started_at,
+        paused_ms: active.paused_ms(chrono::Utc::now().timestamp_millis()),
+        last_error: active.last_error.clone(),
+        warning_count: active.warning_count,
+        live_stream_url: active.live.as_ref().map(|live| live.url.clone()),
+    })
+}
+
 pub fn get_active_streams() -> Vec<String> {
     let manager = PIPELINE_MANAGER.lock().unwrap();
     manager.pipelines.keys().cloned().collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::live_stream::default_live_stream_options;
+    use crate::api::thumbnails::SeekMode;
+    use std::net::UdpSocket;
+    use std::path::PathBuf;
+    use std::time::Instant;
+
+    // Needs GStreamer with the base, good and x264 (or another H.264) plugins;
+    // records videotestsrc, so no display or camera is involved.
+
+    // Spaces, '!' and '%' would break a launch string or a printf pattern
+    fn awkward_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("workahub {} 100% done! {}", test, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn record_test_source(id: &str, sink_path: &Path, secs: u64) -> RecordingResult {
+        let config = RecordingConfig { source: CaptureSource::Test, ..RecordingConfig::default() };
+        start_recording(id.to_string(), sink_path.to_string_lossy().to_string(), config).unwrap();
+        thread::sleep(Duration::from_secs(secs));
+        stop_pipeline(id.to_string()).unwrap()
+    }
+
+    // Plays `udpsrc` on a free local port, as a stand-in live ingest
+    fn udp_receiver() -> (Pipeline, AppSink, u16) {
+        let port = UdpSocket::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let pipeline = Pipeline::new();
+        let src = ElementFactory::make("udpsrc")
+            .property("address", "127.0.0.1")
+            .property("port", port as i32)
+            .build()
+            .unwrap();
+        let sink = AppSink::builder().sync(false).build();
+        add_chain(&pipeline, &[&src, sink.upcast_ref::<Element>()]).unwrap();
+        pipeline.set_state(State::Playing).unwrap();
+        (pipeline, sink, port)
+    }
+
+    fn wait_for_status(id: &str, done: impl Fn(&RecordingStatus) -> bool) -> RecordingStatus {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            let status = get_recording_status(id.to_string()).unwrap();
+            if done(&status) || Instant::now() > deadline {
+                return status;
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+    }
+
+    #[test]
+    fn segment_pattern_escapes_percent() {
+        assert_eq!(segment_pattern("dir/rec.mp4", "mp4"), "dir/rec_%05d.mp4");
+        assert_eq!(segment_pattern("rec.webm", "webm"), "rec_%05d.webm");
+        assert_eq!(
+            segment_pattern("/tmp/my 100% dir!/50% take!.mp4", "mp4"),
+            "/tmp/my 100%% dir!/50%% take!_%05d.mp4"
+        );
+    }
+
+    #[test]
+    fn records_and_thumbnails_in_awkward_directory() {
+        let dir = awkward_dir("record");
+        let result = record_test_source("test-awkward-dir", &dir.join("50% take 1!.mp4"), 3);
+
+        assert!(!result.segments.is_empty());
+        for segment in &result.segments {
+            assert!(Path::new(segment).starts_with(&dir), "{} is outside {}", segment, dir.display());
+            assert!(std::fs::metadata(segment).unwrap().len() > 0, "{} is empty", segment);
+        }
+        let segment = result.segments[0].clone();
+        assert!(segment.ends_with("50% take 1!_00000.mp4"), "unexpected segment name {}", segment);
+
+        for seek in [SeekMode::KeyUnit, SeekMode::Accurate] {
+            let options = VideoThumbnailOptions { position_ms: 1_000, seek, ..VideoThumbnailOptions::default() };
+            let thumbnail = render_video_thumbnail(segment.clone(), options).unwrap();
+            assert_eq!(thumbnail.height, 360);
+            assert!(thumbnail.bytes.starts_with(&[0xFF, 0xD8]), "not a JPEG");
+        }
+
+        let output = dir.join("thumb 100%!.jpg");
+        generate_video_thumbnail(segment, output.to_string_lossy().to_string(), 500).unwrap();
+        assert!(std::fs::read(&output).unwrap().starts_with(&[0xFF, 0xD8]));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn stops_with_snapshot_branch() {
+        let dir = awkward_dir("snapshot");
+        let id = "test-snapshot-stop".to_string();
+        let config = RecordingConfig { source: CaptureSource::Test, ..RecordingConfig::default() };
+        start_recording(id.clone(), dir.join("snapshot.mp4").to_string_lossy().to_string(), config).unwrap();
+        thread::sleep(Duration::from_secs(1));
+
+        let snapshot = capture_live_snapshot(id.clone(), SnapshotFormat::Rgba, 2_000).unwrap();
+        assert_eq!((snapshot.width, snapshot.height), (1280, 720));
+        assert_eq!(snapshot.bytes.len(), (snapshot.stride * snapshot.height) as usize);
+        thread::sleep(Duration::from_secs(1));
+
+        // EOS has to get past the shut valve for the recording to finalize
+        let result = stop_pipeline(id).unwrap();
+        assert!(!result.segments.is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn dead_live_endpoint_leaves_recording_running() {
+        gstreamer::init().unwrap();
+        let dir = awkward_dir("live");
+        let id = "test-live-endpoint".to_string();
+        let (receiver, received, port) = udp_receiver();
+
+        let config = RecordingConfig {
+            source: CaptureSource::Test,
+            live: Some(default_live_stream_options(format!("udp://127.0.0.1:{}", port))),
+            ..RecordingConfig::default()
+        };
+        start_recording(id.clone(), dir.join("live.mp4").to_string_lossy().to_string(), config).unwrap();
+        assert!(received.try_pull_sample(ClockTime::from_seconds(10)).is_some(), "nothing reached the UDP receiver");
+        let _ = receiver.set_state(State::Null);
+
+        // Nothing listens on port 1, so the RTMP connection is refused
+        stop_live_stream(id.clone()).unwrap();
+        start_live_stream(id.clone(), default_live_stream_options("rtmp://127.0.0.1:1/live".to_string())).unwrap();
+        let status = wait_for_status(&id, |status| status.live_stream_url.is_none());
+        assert_eq!(status.live_stream_url, None, "the dead endpoint was never dropped");
+
+        thread::sleep(Duration::from_secs(1));
+        let status = get_recording_status(id.clone()).unwrap();
+        assert_eq!(status.state, RecordingState::Recording);
+
+        let result = stop_pipeline(id).unwrap();
+        assert!(!result.segments.is_empty());
+        for segment in &result.segments {
+            assert!(std::fs::metadata(segment).unwrap().len() > 0, "{} is empty", segment);
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -2,5 +2,16 @@ pub mod simple;
 pub mod db;
 pub mod auth;
 pub mod monitor;
+pub mod screenshot;
+pub mod redaction;
+pub mod change_detection;
+pub mod scheduler;
 pub mod sync;
 pub mod media;
+pub mod encoders;
+pub mod audio;
+pub mod camera;
+pub mod recording_config;
+pub mod capture_region;
+pub mod live_stream;
+pub mod thumbnails;
@@ -1,24 +1,30 @@
 use flutter_rust_bridge::frb;
 use std::sync::{Arc, Mutex};
 use std::thread;
-use screenshots::Screen;
+use std::time::Instant;
 use sysinfo::System;
 use rdev::{listen, Event, EventType};
-use image::ImageFormat;
-use std::io::Cursor;
 use flatbuffers::FlatBufferBuilder;
+use crate::api::scheduler::configured_redaction;
+use crate::api::screenshot::{capture_screenshots, CaptureTarget, ScreenshotOptions};
+use crate::schema::MONITORING_SCHEMA_VERSION;
 use crate::schema::monitoring_generated::workahub::monitoring::{
     InputStatsArgs, SystemStatsArgs, MonitoringPacket, MonitoringPacketArgs,
-    InputStats as FbsInputStats, SystemStats as FbsSystemStats
+    InputStats as FbsInputStats, SystemStats as FbsSystemStats,
+    finish_size_prefixed_monitoring_packet_buffer,
 };
 
 // Global state for monitoring
 lazy_static::lazy_static! {
     static ref INPUT_STATS: Arc<Mutex<InputStats>> = Arc::new(Mutex::new(InputStats::default()));
     static ref SYSTEM: Arc<Mutex<System>> = Arc::new(Mutex::new(System::new_all()));
+    // Last pointer position seen by the input listener, used to pick the active display
+    static ref LAST_CURSOR: Mutex<Option<(f64, f64)>> = Mutex::new(None);
+    // Time of the last keyboard/mouse event, for idle detection
+    static ref LAST_INPUT_AT: Mutex<Option<Instant>> = Mutex::new(None);
 }
 
-#[derive(Default, Clone, Debug)]
+#[derive(Default, Clone, Debug, PartialEq)]
 pub struct InputStats {
     pub mouse_clicks: u64,
     pub key_presses: u64,
@@ -35,15 +41,29 @@ pub fn start_input_monitoring() {
 }
 
 fn callback(event: Event) {
+    *LAST_INPUT_AT.lock().unwrap() = Some(Instant::now());
     let mut stats = INPUT_STATS.lock().unwrap();
     match event.event_type {
         EventType::KeyPress(_) => stats.key_presses += 1,
         EventType::ButtonPress(_) => stats.mouse_clicks += 1,
-        EventType::MouseMove { .. } => stats.mouse_moves += 1,
+        EventType::MouseMove { x, y } => {
+            stats.mouse_moves += 1;
+            *LAST_CURSOR.lock().unwrap() = Some((x, y));
+        }
         _ => (),
     }
 }
 
+// Only known once `start_input_monitoring` has seen the mouse move
+pub(crate) fn last_cursor_position() -> Option<(f64, f64)> {
+    *LAST_CURSOR.lock().unwrap()
+}
+
+// None until `start_input_monitoring` has seen any input
+pub(crate) fn idle_seconds() -> Option<u64> {
+    LAST_INPUT_AT.lock().unwrap().map(|at| at.elapsed().as_secs())
+}
+
 // Get and Reset Stats
 pub fn get_and_reset_input_stats() -> InputStats {
     let mut stats = INPUT_STATS.lock().unwrap();
@@ -53,6 +73,7 @@ pub fn get_and_reset_input_stats() -> InputStats {
 }
 
 // System Stats Structure
+#[derive(Clone, Debug, PartialEq)]
 pub struct SystemStats {
     pub cpu_usage: f32,
     pub memory_used: u64,
@@ -72,19 +93,25 @@ pub fn get_system_stats() -> SystemStats {
 }
 
 // FLATBUFFERS: Zero-copy friendly serialization for high frequency monitoring
+// Packets are size-prefixed and tagged with the "WHMP" identifier so they can be
+// appended to one stream/file and read back with `schema::stream::PacketFrames`.
 pub fn get_monitoring_packet_fbs() -> Vec<u8> {
-    let mut builder = FlatBufferBuilder::new();
-    
-    // 1. Get Input Stats
     let input_stats = get_and_reset_input_stats();
+    let sys_stats = get_system_stats();
+    encode_monitoring_packet(&input_stats, &sys_stats, chrono::Utc::now().timestamp_millis())
+}
+
+pub(crate) fn encode_monitoring_packet(input_stats: &InputStats, sys_stats: &SystemStats, timestamp: i64) -> Vec<u8> {
+    let mut builder = FlatBufferBuilder::new();
+
+    // 1. Input Stats
     let input_offset = FbsInputStats::create(&mut builder, &InputStatsArgs {
         mouse_clicks: input_stats.mouse_clicks,
         key_presses: input_stats.key_presses,
         mouse_moves: input_stats.mouse_moves,
     });
 
-    // 2. Get System Stats
-    let sys_stats = get_system_stats();
+    // 2. System Stats
     let sys_offset = FbsSystemStats::create(&mut builder, &SystemStatsArgs {
         cpu_usage: sys_stats.cpu_usage,
         memory_used: sys_stats.memory_used,
@@ -92,33 +119,31 @@ pub fn get_monitoring_packet_fbs() -> Vec<u8> {
     });
 
     // 3. Create Packet
-    let timestamp = chrono::Utc::now().timestamp_millis();
     let packet = MonitoringPacket::create(&mut builder, &MonitoringPacketArgs {
         input: Some(input_offset),
         system: Some(sys_offset),
         timestamp,
+        schema_version: MONITORING_SCHEMA_VERSION,
     });
 
-    builder.finish(packet, None);
+    finish_size_prefixed_monitoring_packet_buffer(&mut builder, packet);
     builder.finished_data().to_vec()
 }
 
-// Capture Screenshots (Legacy - prefer Media API for video)
+// Capture Screenshots (Legacy - prefer screenshot::capture_screenshots, or the Media API for video).
+// PNGs of every display, redacted with the screenshot policy's RedactionPolicy
+// like scheduled captures.
 pub fn capture_screens() -> Vec<Vec<u8>> {
-    let screens = Screen::all().unwrap_or_default();
-    let mut images = Vec::new();
-
-    for screen in screens {
-        match screen.capture() {
-            Ok(image) => {
-                let dynamic_image = image::DynamicImage::ImageRgba8(image);
-                let mut buffer = Vec::new();
-                if let Ok(_) = dynamic_image.write_to(&mut Cursor::new(&mut buffer), ImageFormat::Png) {
-                    images.push(buffer);
-                }
-            },
-            Err(e) => println!("Failed to capture screen: {}", e),
+    let options = ScreenshotOptions {
+        redaction: configured_redaction(),
+        thumbnail_max_dimension: None,
+        ..ScreenshotOptions::default()
+    };
+    match capture_screenshots(CaptureTarget::AllDisplays, options) {
+        Ok(captures) => captures.into_iter().map(|capture| capture.bytes).collect(),
+        Err(e) => {
+            println!("Failed to capture screens: {}", e);
+            Vec::new()
         }
     }
-    images
-}
\ No newline at end of file
+}
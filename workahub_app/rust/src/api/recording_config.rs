@@ -0,0 +1,212 @@
+use flutter_rust_bridge::frb;
+use anyhow::{Result, anyhow};
+use crate::api::audio::{AudioCodec, AudioOptions};
+use crate::api::camera::PictureInPicture;
+use crate::api::capture_region::find_window;
+use crate::api::encoders::VideoCodec;
+use crate::api::live_stream::LiveStreamOptions;
+use crate::api::media::CaptureSource;
+use crate::api::screenshot::list_displays;
+
+// Everything that shapes a recording, validated before any pipeline is built
+// so Flutter gets one clear error instead of a negotiation failure mid-start.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RecordingContainer {
+    Mp4,
+    Mkv,
+    WebM,
+}
+
+impl RecordingContainer {
+    pub(crate) fn muxer(&self) -> &'static str {
+        match self {
+            RecordingContainer::Mp4 => "mp4mux",
+            RecordingContainer::Mkv => "matroskamux",
+            RecordingContainer::WebM => "webmmux",
+        }
+    }
+
+    pub(crate) fn extension(&self) -> &'static str {
+        match self {
+            RecordingContainer::Mp4 => "mp4",
+            RecordingContainer::Mkv => "mkv",
+            RecordingContainer::WebM => "webm",
+        }
+    }
+
+    pub(crate) fn accepts(&self, codec: VideoCodec) -> bool {
+        match self {
+            // mp4mux has no VP8 mapping
+            RecordingContainer::Mp4 => codec != VideoCodec::Vp8,
+            RecordingContainer::Mkv => true,
+            RecordingContainer::WebM => matches!(codec, VideoCodec::Vp8 | VideoCodec::Vp9 | VideoCodec::Av1),
+        }
+    }
+
+    pub(crate) fn audio_codec(&self) -> AudioCodec {
+        match self {
+            RecordingContainer::WebM => AudioCodec::Opus,
+            RecordingContainer::Mp4 | RecordingContainer::Mkv => AudioCodec::Aac,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QualityLevel {
+    Low,
+    Medium,
+    High,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BitrateMode {
+    // Fixed target, in kbit/s
+    Bitrate { kbps: u32 },
+    // Target derived from the output resolution and framerate
+    Quality { level: QualityLevel },
+}
+
+// Rectangle of the captured frame, in source pixels (physical pixels for screens)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RecordingRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl RecordingRegion {
+    pub(crate) fn validate(&self) -> Result<()> {
+        // 4:2:0 encoders need even dimensions
+        if self.width < 16 || self.height < 16 || self.width % 2 != 0 || self.height % 2 != 0 {
+            return Err(anyhow!(
+                "Region must be at least 16x16 with even dimensions, got {}x{}",
+                self.width, self.height
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RecordingConfig {
+    pub source: CaptureSource,
+    // Records only this part of the source; None records the whole frame
+    pub region: Option<RecordingRegion>,
+    pub fps: u32,
+    pub bitrate: BitrateMode,
+    pub keyframe_interval_secs: u32,
+    pub container: RecordingContainer,
+    pub capture_cursor: bool,
+    // Length of each output file
+    pub segment_secs: u32,
+    pub audio: AudioOptions,
+    pub pip: Option<PictureInPicture>,
+    // Also streams to an RTMP/SRT endpoint; can be started later with `start_live_stream`
+    pub live: Option<LiveStreamOptions>,
+}
+
+impl Default for RecordingConfig {
+    fn default() -> Self {
+        Self {
+            source: CaptureSource::Screen,
+            region: None,
+            fps: 30,
+            bitrate: BitrateMode::Bitrate { kbps: 4000 },
+            keyframe_interval_secs: 2,
+            container: RecordingContainer::Mp4,
+            capture_cursor: true,
+            segment_secs: 300,
+            audio: AudioOptions::default(),
+            pip: None,
+            live: None,
+        }
+    }
+}
+
+impl RecordingConfig {
+    pub(crate) fn validate(&self) -> Result<()> {
+        if !(1..=120).contains(&self.fps) {
+            return Err(anyhow!("fps must be between 1 and 120, got {}", self.fps));
+        }
+        if let BitrateMode::Bitrate { kbps } = self.bitrate {
+            if !(100..=100_000).contains(&kbps) {
+                return Err(anyhow!("Bitrate must be between 100 and 100000 kbps, got {}", kbps));
+            }
+        }
+        if !(1..=60).contains(&self.keyframe_interval_secs) {
+            return Err(anyhow!("Keyframe interval must be between 1 and 60 seconds, got {}", self.keyframe_interval_secs));
+        }
+        if self.segment_secs < self.keyframe_interval_secs {
+            return Err(anyhow!("Segments can't be shorter than the keyframe interval"));
+        }
+        if let Some(region) = &self.region {
+            region.validate()?;
+        }
+        if let CaptureSource::Window { id: None, title: None } = &self.source {
+            return Err(anyhow!("Window recordings need a window id or title"));
+        }
+        if self.pip.is_some() && matches!(self.source, CaptureSource::Camera { .. }) {
+            return Err(anyhow!("Picture-in-picture needs a screen source"));
+        }
+        if !(16..=512).contains(&self.audio.bitrate_kbps) {
+            return Err(anyhow!("Audio bitrate must be between 16 and 512 kbps, got {}", self.audio.bitrate_kbps));
+        }
+        if let Some(live) = &self.live {
+            live.validate()?;
+        }
+        Ok(())
+    }
+
+    // Best guess at the encoded frame size before caps are negotiated
+    fn output_size(&self) -> (u32, u32) {
+        if let Some(region) = &self.region {
+            return (region.width, region.height);
+        }
+        let display_size = |primary: bool, id: u32| list_displays().ok()
+            .and_then(|displays| displays.into_iter().find(|d| if primary { d.is_primary } else { d.id == id }))
+            .map(|d| ((d.width as f32 * d.scale_factor) as u32, (d.height as f32 * d.scale_factor) as u32))
+            .unwrap_or((1920, 1080));
+        match &self.source {
+            CaptureSource::Test => (1280, 720),
+            CaptureSource::Screen => display_size(true, 0),
+            CaptureSource::Display { id } => display_size(false, *id),
+            // Logical size; close enough for picking a bitrate
+            CaptureSource::Window { id, title } => find_window(*id, title.as_deref())
+                .map(|w| (w.width, w.height))
+                .unwrap_or((1920, 1080)),
+            CaptureSource::PipeWire { .. } | CaptureSource::Camera { .. } => (1920, 1080),
+        }
+    }
+
+    pub(crate) fn bitrate_kbps(&self) -> u32 {
+        match self.bitrate {
+            BitrateMode::Bitrate { kbps } => kbps,
+            BitrateMode::Quality { level } => {
+                // Bits per pixel per frame; screen content compresses well
+                let bits_per_pixel = match level {
+                    QualityLevel::Low => 0.04,
+                    QualityLevel::Medium => 0.07,
+                    QualityLevel::High => 0.12,
+                };
+                let (width, height) = self.output_size();
+                let bps = width as f64 * height as f64 * self.fps as f64 * bits_per_pixel;
+                ((bps / 1000.0) as u32).clamp(500, 50_000)
+            }
+        }
+    }
+
+    pub(crate) fn keyframe_interval_frames(&self) -> u32 {
+        self.keyframe_interval_secs * self.fps
+    }
+}
+
+pub fn default_recording_config() -> RecordingConfig {
+    RecordingConfig::default()
+}
+
+// Lets the settings screen check a config without starting a recording
+pub fn validate_recording_config(config: RecordingConfig) -> Result<()> {
+    config.validate()
+}
@@ -0,0 +1,167 @@
+use flutter_rust_bridge::frb;
+use anyhow::{Result, anyhow};
+use image::imageops::{self, FilterType};
+use image::{Rgba, RgbaImage};
+use xcap::Window;
+use crate::api::screenshot::DisplayDescriptor;
+
+// Privacy redaction applied to raw captures before they are encoded, so
+// unredacted pixels never reach disk or the upload queue.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlurLevel {
+    None,
+    Light,
+    Medium,
+    Heavy,
+}
+
+impl BlurLevel {
+    // Downscale factor; large enough at Heavy that text is unrecoverable
+    fn factor(&self) -> u32 {
+        match self {
+            BlurLevel::None => 1,
+            BlurLevel::Light => 6,
+            BlurLevel::Medium => 12,
+            BlurLevel::Heavy => 32,
+        }
+    }
+}
+
+// Desktop coordinates in logical pixels, same space as `DisplayDescriptor`
+#[derive(Debug, Clone)]
+pub struct RedactionRect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct RedactionPolicy {
+    // Applied to the whole capture
+    pub full_blur: BlurLevel,
+    // Case-insensitive substrings matched against each window's app name and title
+    pub blurred_apps: Vec<String>,
+    pub app_blur: BlurLevel,
+    // Filled solid black
+    pub masked_rects: Vec<RedactionRect>,
+}
+
+impl Default for RedactionPolicy {
+    fn default() -> Self {
+        Self {
+            full_blur: BlurLevel::None,
+            blurred_apps: Vec::new(),
+            app_blur: BlurLevel::Heavy,
+            masked_rects: Vec::new(),
+        }
+    }
+}
+
+impl RedactionPolicy {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.full_blur == BlurLevel::None && self.blurred_apps.is_empty() && self.masked_rects.is_empty()
+    }
+}
+
+// Blurs by shrinking and re-enlarging, which is cheap even on 4K frames
+// and, unlike a light gaussian, doesn't leave text legible.
+fn blur_image(image: &RgbaImage, level: BlurLevel) -> RgbaImage {
+    let factor = level.factor();
+    if factor <= 1 {
+        return image.clone();
+    }
+    let (width, height) = image.dimensions();
+    let small = imageops::resize(image, (width / factor).max(1), (height / factor).max(1), FilterType::Triangle);
+    imageops::resize(&small, width, height, FilterType::Triangle)
+}
+
+// Maps a desktop rectangle onto the capture's pixel grid (captures of HiDPI
+// displays are larger than their logical geometry). None if it misses the capture.
+fn to_pixels(rect: &RedactionRect, display: &DisplayDescriptor, image: &RgbaImage) -> Option<(u32, u32, u32, u32)> {
+    if display.width == 0 || display.height == 0 {
+        return None;
+    }
+    let scale_x = image.width() as f64 / display.width as f64;
+    let scale_y = image.height() as f64 / display.height as f64;
+
+    let left = ((rect.x - display.x) as f64 * scale_x).floor().max(0.0);
+    let top = ((rect.y - display.y) as f64 * scale_y).floor().max(0.0);
+    let right = (((rect.x - display.x) as f64 + rect.width as f64) * scale_x).ceil().min(image.width() as f64);
+    let bottom = (((rect.y - display.y) as f64 + rect.height as f64) * scale_y).ceil().min(image.height() as f64);
+
+    if right <= left || bottom <= top {
+        return None;
+    }
+    Some((left as u32, top as u32, (right - left) as u32, (bottom - top) as u32))
+}
+
+fn blur_region(image: &mut RgbaImage, (x, y, width, height): (u32, u32, u32, u32), level: BlurLevel) {
+    let region = imageops::crop_imm(image, x, y, width, height).to_image();
+    imageops::replace(image, &blur_image(&region, level), x as i64, y as i64);
+}
+
+fn mask_region(image: &mut RgbaImage, (x, y, width, height): (u32, u32, u32, u32)) {
+    for py in y..y + height {
+        for px in x..x + width {
+            image.put_pixel(px, py, Rgba([0, 0, 0, 255]));
+        }
+    }
+}
+
+// Geometry of every visible window whose app name or title matches the policy
+pub(crate) fn matching_window_rects(policy: &RedactionPolicy) -> Result<Vec<RedactionRect>> {
+    if policy.blurred_apps.is_empty() {
+        return Ok(Vec::new());
+    }
+    let patterns: Vec<String> = policy.blurred_apps.iter().map(|p| p.to_lowercase()).collect();
+    let windows = Window::all().map_err(|e| anyhow!("Failed to enumerate windows: {}", e))?;
+
+    let mut rects = Vec::new();
+    for window in windows {
+        if window.is_minimized().unwrap_or(false) {
+            continue;
+        }
+        let app_name = window.app_name().unwrap_or_default().to_lowercase();
+        let title = window.title().unwrap_or_default().to_lowercase();
+        if !patterns.iter().any(|p| app_name.contains(p.as_str()) || title.contains(p.as_str())) {
+            continue;
+        }
+        if let (Ok(x), Ok(y), Ok(width), Ok(height)) = (window.x(), window.y(), window.width(), window.height()) {
+            rects.push(RedactionRect { x, y, width, height });
+        }
+    }
+    Ok(rects)
+}
+
+// `window_rects` comes from `matching_window_rects`; it is looked up once per
+// capture call rather than once per display. Err means the lookup failed, in
+// which case the whole capture is blurred rather than risk leaking those apps.
+pub(crate) fn apply_redaction(
+    image: &mut RgbaImage,
+    display: &DisplayDescriptor,
+    policy: &RedactionPolicy,
+    window_rects: &Result<Vec<RedactionRect>>,
+) {
+    match window_rects {
+        Ok(rects) => {
+            for rect in rects {
+                if let Some(region) = to_pixels(rect, display, image) {
+                    blur_region(image, region, policy.app_blur);
+                }
+            }
+        }
+        Err(_) => *image = blur_image(image, BlurLevel::Heavy),
+    }
+
+    if policy.full_blur != BlurLevel::None {
+        *image = blur_image(image, policy.full_blur);
+    }
+
+    for rect in &policy.masked_rects {
+        if let Some(region) = to_pixels(rect, display, image) {
+            mask_region(image, region);
+        }
+    }
+}
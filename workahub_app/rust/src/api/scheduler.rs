@@ -0,0 +1,226 @@
+use flutter_rust_bridge::frb;
+use anyhow::{Result, anyhow};
+use lazy_static::lazy_static;
+use rand::Rng;
+use serde_json::json;
+use std::sync::Mutex;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::api::db::{enqueue_upload, store_artifact};
+use crate::api::monitor::idle_seconds;
+use crate::api::redaction::RedactionPolicy;
+use crate::api::screenshot::{capture_screenshots, CaptureTarget, ScreenshotOptions};
+
+// Background screenshot schedule. Each interval is split into `shots_per_interval`
+// equal slots and one capture happens at a random moment inside each slot, so the
+// user can't predict when a screenshot is taken but shots never bunch together.
+
+lazy_static! {
+    static ref SCHEDULER: Mutex<SchedulerState> = Mutex::new(SchedulerState::default());
+}
+
+// Set per organization and pushed down from Flutter after login
+#[derive(Debug, Clone)]
+pub struct ScreenshotPolicy {
+    pub interval_secs: u32,
+    pub shots_per_interval: u32,
+    pub target: CaptureTarget,
+    pub options: ScreenshotOptions,
+    // Skip captures after this many seconds without input; 0 disables idle detection
+    pub idle_threshold_secs: u32,
+}
+
+pub struct SchedulerStatus {
+    pub running: bool,
+    pub paused: bool,
+    pub on_break: bool,
+    pub idle: bool,
+    // Unix epoch milliseconds of the next planned capture
+    pub next_capture_at: Option<i64>,
+    pub captured_count: u64,
+    pub skipped_count: u64,
+    pub last_error: Option<String>,
+}
+
+#[derive(Default)]
+struct SchedulerState {
+    stop_tx: Option<Sender<()>>,
+    policy: Option<ScreenshotPolicy>,
+    paused: bool,
+    on_break: bool,
+    next_capture_at: Option<i64>,
+    captured_count: u64,
+    skipped_count: u64,
+    last_error: Option<String>,
+}
+
+fn is_idle(policy: &ScreenshotPolicy) -> bool {
+    policy.idle_threshold_secs > 0
+        && idle_seconds().is_some_and(|secs| secs >= policy.idle_threshold_secs as u64)
+}
+
+// Offsets from the start of an interval, one random point per slot
+fn random_offsets(interval: Duration, shots: u32) -> Vec<Duration> {
+    if shots == 0 {
+        return Vec::new();
+    }
+    let slot_ms = (interval.as_millis() as u64 / shots as u64).max(1);
+    let mut rng = rand::rng();
+    (0..shots as u64)
+        .map(|slot| Duration::from_millis(slot * slot_ms + rng.random_range(0..slot_ms)))
+        .collect()
+}
+
+// Returns true if the scheduler was asked to stop while waiting
+fn wait_until(deadline: Instant, stop_rx: &Receiver<()>) -> bool {
+    let timeout = deadline.saturating_duration_since(Instant::now());
+    !matches!(stop_rx.recv_timeout(timeout), Err(RecvTimeoutError::Timeout))
+}
+
+fn capture_and_store(policy: &ScreenshotPolicy) -> Result<usize> {
+    let captures = capture_screenshots(policy.target.clone(), policy.options.clone())?;
+    for capture in &captures {
+        let metadata = json!({
+            "display_id": capture.display_id,
+            "display_name": capture.display_name,
+            "x": capture.x,
+            "y": capture.y,
+            "width": capture.width,
+            "height": capture.height,
+            "scale_factor": capture.scale_factor,
+            "image_width": capture.image_width,
+            "image_height": capture.image_height,
+            "timestamp": capture.timestamp,
+            "perceptual_hash": format!("{:016x}", capture.perceptual_hash),
+            "change_distance": capture.change_distance,
+            "is_duplicate": capture.is_duplicate,
+        });
+        let artifact = store_artifact("screenshot", capture.format.extension(), &capture.bytes, Some(metadata.to_string()), None)?;
+        enqueue_upload(artifact.id)?;
+
+        if let Some(thumbnail) = &capture.thumbnail {
+            let metadata = json!({
+                "width": thumbnail.width,
+                "height": thumbnail.height,
+                "timestamp": capture.timestamp,
+            });
+            let thumb = store_artifact(
+                "screenshot_thumbnail",
+                thumbnail.format.extension(),
+                &thumbnail.bytes,
+                Some(metadata.to_string()),
+                Some(artifact.id),
+            )?;
+            enqueue_upload(thumb.id)?;
+        }
+    }
+    Ok(captures.len())
+}
+
+fn run_scheduler(policy: ScreenshotPolicy, stop_rx: Receiver<()>) {
+    let interval = Duration::from_secs(policy.interval_secs.max(1) as u64);
+    loop {
+        let interval_start = Instant::now();
+        for offset in random_offsets(interval, policy.shots_per_interval) {
+            let at = interval_start + offset;
+            let wait = at.saturating_duration_since(Instant::now());
+            SCHEDULER.lock().unwrap().next_capture_at =
+                Some(chrono::Utc::now().timestamp_millis() + wait.as_millis() as i64);
+            if wait_until(at, &stop_rx) {
+                return;
+            }
+
+            let skip = {
+                let state = SCHEDULER.lock().unwrap();
+                state.paused || state.on_break || is_idle(&policy)
+            };
+            if skip {
+                SCHEDULER.lock().unwrap().skipped_count += 1;
+                continue;
+            }
+
+            // Capture without holding the lock so status queries stay responsive
+            let result = capture_and_store(&policy);
+            let mut state = SCHEDULER.lock().unwrap();
+            match result {
+                Ok(count) => state.captured_count += count as u64,
+                Err(e) => {
+                    println!("Scheduled screenshot failed: {}", e);
+                    state.last_error = Some(e.to_string());
+                }
+            }
+        }
+        if wait_until(interval_start + interval, &stop_rx) {
+            return;
+        }
+    }
+}
+
+// Starts (or restarts with a new policy) the background screenshot schedule
+pub fn start_screenshot_scheduler(policy: ScreenshotPolicy) -> Result<String> {
+    if policy.interval_secs == 0 {
+        return Err(anyhow!("Screenshot interval must be at least 1 second"));
+    }
+
+    let mut state = SCHEDULER.lock().map_err(|e| anyhow!("Lock error: {}", e))?;
+    if let Some(stop_tx) = state.stop_tx.take() {
+        let _ = stop_tx.send(());
+    }
+
+    let (stop_tx, stop_rx) = mpsc::channel();
+    let worker_policy = policy.clone();
+    thread::spawn(move || run_scheduler(worker_policy, stop_rx));
+
+    let message = format!(
+        "Screenshot scheduler started: {} per {}s",
+        policy.shots_per_interval, policy.interval_secs
+    );
+    state.stop_tx = Some(stop_tx);
+    state.policy = Some(policy);
+    state.next_capture_at = None;
+    state.last_error = None;
+    Ok(message)
+}
+
+pub fn stop_screenshot_scheduler() -> Result<String> {
+    let mut state = SCHEDULER.lock().map_err(|e| anyhow!("Lock error: {}", e))?;
+    match state.stop_tx.take() {
+        Some(stop_tx) => {
+            let _ = stop_tx.send(());
+            state.next_capture_at = None;
+            Ok("Screenshot scheduler stopped".to_string())
+        }
+        None => Err(anyhow!("Screenshot scheduler is not running")),
+    }
+}
+
+// Redaction of the last policy pushed down, kept after the scheduler stops so
+// ad-hoc captures stay redacted too
+pub(crate) fn configured_redaction() -> RedactionPolicy {
+    let state = SCHEDULER.lock().unwrap();
+    state.policy.as_ref().map(|policy| policy.options.redaction.clone()).unwrap_or_default()
+}
+
+// Paused and on-break slots are skipped, not deferred
+pub fn set_screenshots_paused(paused: bool) {
+    SCHEDULER.lock().unwrap().paused = paused;
+}
+
+pub fn set_on_break(on_break: bool) {
+    SCHEDULER.lock().unwrap().on_break = on_break;
+}
+
+pub fn get_screenshot_scheduler_status() -> SchedulerStatus {
+    let state = SCHEDULER.lock().unwrap();
+    SchedulerStatus {
+        running: state.stop_tx.is_some(),
+        paused: state.paused,
+        on_break: state.on_break,
+        idle: state.policy.as_ref().is_some_and(is_idle),
+        next_capture_at: state.next_capture_at,
+        captured_count: state.captured_count,
+        skipped_count: state.skipped_count,
+        last_error: state.last_error.clone(),
+    }
+}
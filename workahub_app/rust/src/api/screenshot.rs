@@ -0,0 +1,371 @@
+use flutter_rust_bridge::frb;
+use anyhow::{Result, anyhow};
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::codecs::webp::{WebPEncoder, WebPQuality};
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageEncoder, RgbaImage};
+use screenshots::Screen;
+use crate::api::change_detection::{check_and_record, dhash, ChangeDetection};
+use crate::api::monitor::last_cursor_position;
+use crate::api::redaction::{apply_redaction, matching_window_rects, RedactionPolicy};
+
+// Which part of the desktop a screenshot call should cover
+#[derive(Debug, Clone)]
+pub enum CaptureTarget {
+    // One capture per connected display
+    AllDisplays,
+    // A single display, by the id reported in `list_displays`
+    Display { id: u32 },
+    // The display the mouse was last seen on (primary display if unknown)
+    UnderCursor,
+    // All displays stitched into one image using their desktop coordinates
+    VirtualDesktop,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScreenshotFormat {
+    Png,
+    Jpeg,
+    WebP,
+}
+
+impl ScreenshotFormat {
+    pub(crate) fn extension(&self) -> &'static str {
+        match self {
+            ScreenshotFormat::Png => "png",
+            ScreenshotFormat::Jpeg => "jpg",
+            ScreenshotFormat::WebP => "webp",
+        }
+    }
+}
+
+// Lets an organization trade fidelity for upload size
+#[derive(Debug, Clone)]
+pub struct ScreenshotOptions {
+    pub format: ScreenshotFormat,
+    // 1-100, used by JPEG and WebP. 100 gives lossless WebP; PNG is always lossless.
+    pub quality: u8,
+    // Longest side in pixels; larger captures are downscaled keeping aspect ratio
+    pub max_dimension: Option<u32>,
+    pub grayscale: bool,
+    // Applied to the full-resolution capture, before anything is encoded or stored
+    pub redaction: RedactionPolicy,
+    // None hashes every capture but never flags or drops any
+    pub change_detection: Option<ChangeDetection>,
+    // Longest side of the companion thumbnail; None skips thumbnails
+    pub thumbnail_max_dimension: Option<u32>,
+}
+
+impl Default for ScreenshotOptions {
+    fn default() -> Self {
+        Self {
+            format: ScreenshotFormat::Png,
+            quality: 80,
+            max_dimension: None,
+            grayscale: false,
+            redaction: RedactionPolicy::default(),
+            change_detection: None,
+            thumbnail_max_dimension: Some(320),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DisplayDescriptor {
+    pub id: u32,
+    pub name: String,
+    // Desktop coordinates in logical pixels
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub scale_factor: f32,
+    pub is_primary: bool,
+}
+
+// Small JPEG preview for timelines, generated from the same redacted frame
+pub struct ScreenThumbnail {
+    pub width: u32,
+    pub height: u32,
+    pub format: ScreenshotFormat,
+    pub bytes: Vec<u8>,
+}
+
+pub struct ScreenCapture {
+    // None for a stitched virtual desktop image
+    pub display_id: Option<u32>,
+    pub display_name: String,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub scale_factor: f32,
+    // Size of the encoded image: physical pixels on HiDPI displays, unless downscaled
+    pub image_width: u32,
+    pub image_height: u32,
+    // Unix epoch milliseconds, taken right after the frame was grabbed
+    pub timestamp: i64,
+    pub format: ScreenshotFormat,
+    pub bytes: Vec<u8>,
+    // dHash of the (redacted) capture
+    pub perceptual_hash: u64,
+    // Hamming distance to the previous kept capture of this display
+    pub change_distance: Option<u32>,
+    pub is_duplicate: bool,
+    pub thumbnail: Option<ScreenThumbnail>,
+}
+
+// A grabbed frame before encoding
+struct RawCapture {
+    display: DisplayDescriptor,
+    // Set for a virtual desktop image, whose descriptor covers all displays
+    stitched: bool,
+    image: RgbaImage,
+    timestamp: i64,
+}
+
+fn describe(screen: &Screen) -> DisplayDescriptor {
+    let info = screen.display_info;
+    // display-info exposes no human readable name, so derive a stable one
+    let name = if info.is_primary {
+        format!("Display {} (primary)", info.id)
+    } else {
+        format!("Display {}", info.id)
+    };
+    DisplayDescriptor {
+        id: info.id,
+        name,
+        x: info.x,
+        y: info.y,
+        width: info.width,
+        height: info.height,
+        scale_factor: info.scale_factor,
+        is_primary: info.is_primary,
+    }
+}
+
+pub fn list_displays() -> Result<Vec<DisplayDescriptor>> {
+    let screens = Screen::all().map_err(|e| anyhow!("Failed to enumerate displays: {}", e))?;
+    Ok(screens.iter().map(describe).collect())
+}
+
+fn grab(screen: &Screen) -> Result<RawCapture> {
+    let image = screen.capture()
+        .map_err(|e| anyhow!("Failed to capture display {}: {}", screen.display_info.id, e))?;
+    Ok(RawCapture {
+        display: describe(screen),
+        stitched: false,
+        image,
+        timestamp: chrono::Utc::now().timestamp_millis(),
+    })
+}
+
+fn screen_under_cursor() -> Result<Screen> {
+    if let Some((x, y)) = last_cursor_position() {
+        if let Ok(screen) = Screen::from_point(x as i32, y as i32) {
+            return Ok(screen);
+        }
+    }
+    let screens = Screen::all().map_err(|e| anyhow!("Failed to enumerate displays: {}", e))?;
+    let fallback = screens.iter().position(|s| s.display_info.is_primary).unwrap_or(0);
+    screens.into_iter().nth(fallback).ok_or_else(|| anyhow!("No displays found"))
+}
+
+// Places every display at its desktop position. Captures are in physical pixels,
+// so HiDPI displays are scaled down to their logical size first.
+fn stitch(captures: Vec<RawCapture>) -> Result<RawCapture> {
+    let displays: Vec<&DisplayDescriptor> = captures.iter().map(|c| &c.display).collect();
+    let min_x = displays.iter().map(|d| d.x).min().ok_or_else(|| anyhow!("No displays captured"))?;
+    let min_y = displays.iter().map(|d| d.y).min().unwrap_or(0);
+    let max_x = displays.iter().map(|d| d.x + d.width as i32).max().unwrap_or(0);
+    let max_y = displays.iter().map(|d| d.y + d.height as i32).max().unwrap_or(0);
+
+    let desktop = DisplayDescriptor {
+        id: 0,
+        name: "Virtual desktop".to_string(),
+        x: min_x,
+        y: min_y,
+        width: (max_x - min_x) as u32,
+        height: (max_y - min_y) as u32,
+        scale_factor: 1.0,
+        is_primary: false,
+    };
+
+    let mut canvas = RgbaImage::new(desktop.width, desktop.height);
+    let mut timestamp = 0;
+    for capture in captures {
+        let display = capture.display;
+        let image = if capture.image.dimensions() != (display.width, display.height) {
+            image::imageops::resize(&capture.image, display.width, display.height, image::imageops::FilterType::Triangle)
+        } else {
+            capture.image
+        };
+        image::imageops::overlay(&mut canvas, &image, (display.x - min_x) as i64, (display.y - min_y) as i64);
+        timestamp = timestamp.max(capture.timestamp);
+    }
+
+    Ok(RawCapture { display: desktop, stitched: true, image: canvas, timestamp })
+}
+
+fn capture_raw(target: &CaptureTarget) -> Result<Vec<RawCapture>> {
+    match target {
+        CaptureTarget::AllDisplays => {
+            let screens = Screen::all().map_err(|e| anyhow!("Failed to enumerate displays: {}", e))?;
+            let mut captures = Vec::new();
+            for screen in &screens {
+                match grab(screen) {
+                    Ok(capture) => captures.push(capture),
+                    // A display that fails (e.g. asleep) shouldn't lose the others
+                    Err(e) => println!("{}", e),
+                }
+            }
+            Ok(captures)
+        }
+        CaptureTarget::Display { id } => {
+            let screens = Screen::all().map_err(|e| anyhow!("Failed to enumerate displays: {}", e))?;
+            let screen = screens.iter().find(|s| s.display_info.id == *id)
+                .ok_or_else(|| anyhow!("Display {} not found", id))?;
+            Ok(vec![grab(screen)?])
+        }
+        CaptureTarget::UnderCursor => Ok(vec![grab(&screen_under_cursor()?)?]),
+        CaptureTarget::VirtualDesktop => {
+            let screens = Screen::all().map_err(|e| anyhow!("Failed to enumerate displays: {}", e))?;
+            let captures = screens.iter().map(grab).collect::<Result<Vec<_>>>()?;
+            Ok(vec![stitch(captures)?])
+        }
+    }
+}
+
+// Lossy WebP goes through libwebp (the `webp-encoder` feature), which image marks deprecated
+#[allow(deprecated)]
+fn lossy_webp_encoder<W: std::io::Write>(writer: W, quality: u8) -> WebPEncoder<W> {
+    WebPEncoder::new_with_quality(writer, WebPQuality::lossy(quality))
+}
+
+// Applies the size/colour options, then encodes with the requested codec
+pub(crate) fn encode_image(image: DynamicImage, options: &ScreenshotOptions) -> Result<(Vec<u8>, u32, u32)> {
+    let mut image = image;
+    if let Some(max) = options.max_dimension {
+        if max > 0 && (image.width() > max || image.height() > max) {
+            image = image.resize(max, max, FilterType::Triangle);
+        }
+    }
+    if options.grayscale {
+        image = DynamicImage::ImageLuma8(image.to_luma8());
+    }
+
+    let (width, height) = (image.width(), image.height());
+    let quality = options.quality.clamp(1, 100);
+    let mut bytes = Vec::new();
+    let result = match options.format {
+        ScreenshotFormat::Png => {
+            let (buf, color) = if options.grayscale {
+                (image.into_luma8().into_raw(), image::ColorType::L8)
+            } else {
+                (image.into_rgba8().into_raw(), image::ColorType::Rgba8)
+            };
+            PngEncoder::new(&mut bytes).write_image(&buf, width, height, color)
+        }
+        ScreenshotFormat::Jpeg => {
+            // JPEG has no alpha channel
+            let (buf, color) = if options.grayscale {
+                (image.into_luma8().into_raw(), image::ColorType::L8)
+            } else {
+                (image.into_rgb8().into_raw(), image::ColorType::Rgb8)
+            };
+            JpegEncoder::new_with_quality(&mut bytes, quality).write_image(&buf, width, height, color)
+        }
+        ScreenshotFormat::WebP => {
+            // The WebP encoder only takes RGB(A); grayscale stays grey in RGB
+            let buf = image.into_rgb8().into_raw();
+            let encoder = if quality == 100 {
+                WebPEncoder::new_lossless(&mut bytes)
+            } else {
+                lossy_webp_encoder(&mut bytes, quality)
+            };
+            encoder.write_image(&buf, width, height, image::ColorType::Rgb8)
+        }
+    };
+    result.map_err(|e| anyhow!("Failed to encode screenshot: {}", e))?;
+
+    Ok((bytes, width, height))
+}
+
+const THUMBNAIL_QUALITY: u8 = 70;
+
+fn make_thumbnail(image: &RgbaImage, max_dimension: u32, grayscale: bool) -> Result<ScreenThumbnail> {
+    let (width, height) = image.dimensions();
+    let scale = (max_dimension as f64 / width.max(height) as f64).min(1.0);
+    let thumb_width = ((width as f64 * scale).round() as u32).max(1);
+    let thumb_height = ((height as f64 * scale).round() as u32).max(1);
+    // Shrink first so the JPEG encoder never sees the full frame
+    let small = image::imageops::thumbnail(image, thumb_width, thumb_height);
+
+    let options = ScreenshotOptions {
+        format: ScreenshotFormat::Jpeg,
+        quality: THUMBNAIL_QUALITY,
+        grayscale,
+        ..Default::default()
+    };
+    let (bytes, width, height) = encode_image(DynamicImage::ImageRgba8(small), &options)?;
+    Ok(ScreenThumbnail { width, height, format: ScreenshotFormat::Jpeg, bytes })
+}
+
+fn encode(raw: RawCapture, options: &ScreenshotOptions, change: (u64, Option<u32>, bool)) -> Result<ScreenCapture> {
+    let (perceptual_hash, change_distance, is_duplicate) = change;
+    let thumbnail = match options.thumbnail_max_dimension {
+        Some(max) if max > 0 => Some(make_thumbnail(&raw.image, max, options.grayscale)?),
+        _ => None,
+    };
+    let (bytes, image_width, image_height) = encode_image(DynamicImage::ImageRgba8(raw.image), options)?;
+
+    let display = raw.display;
+    Ok(ScreenCapture {
+        display_id: if raw.stitched { None } else { Some(display.id) },
+        display_name: display.name,
+        x: display.x,
+        y: display.y,
+        width: display.width,
+        height: display.height,
+        scale_factor: display.scale_factor,
+        image_width,
+        image_height,
+        timestamp: raw.timestamp,
+        format: options.format,
+        bytes,
+        perceptual_hash,
+        change_distance,
+        is_duplicate,
+        thumbnail,
+    })
+}
+
+pub fn capture_screenshots(target: CaptureTarget, options: ScreenshotOptions) -> Result<Vec<ScreenCapture>> {
+    let mut captures = capture_raw(&target)?;
+    if !options.redaction.is_empty() {
+        let window_rects = matching_window_rects(&options.redaction);
+        if let Err(e) = &window_rects {
+            println!("{}; blurring whole capture", e);
+        }
+        for raw in &mut captures {
+            apply_redaction(&mut raw.image, &raw.display, &options.redaction, &window_rects);
+        }
+    }
+
+    let mut results = Vec::new();
+    for raw in captures {
+        // Hash before encoding so dropped duplicates cost no encode time
+        let hash = dhash(&raw.image);
+        let display_id = if raw.stitched { None } else { Some(raw.display.id) };
+        let (distance, is_duplicate) = match &options.change_detection {
+            Some(config) => check_and_record(display_id, hash, config),
+            None => (None, false),
+        };
+        if is_duplicate && options.change_detection.as_ref().is_some_and(|c| c.drop_duplicates) {
+            continue;
+        }
+        results.push(encode(raw, &options, (hash, distance, is_duplicate))?);
+    }
+    Ok(results)
+}
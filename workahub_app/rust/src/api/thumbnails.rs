@@ -0,0 +1,420 @@
+use flutter_rust_bridge::frb;
+use anyhow::{Result, anyhow};
+use gstreamer::prelude::*;
+use gstreamer::{Caps, ClockTime, Element, ElementFactory, MessageType, MessageView, Pipeline, SeekFlags, State};
+use gstreamer_app::AppSink;
+use image::ImageEncoder;
+use std::path::Path;
+use crate::api::media::{encode_jpeg, rgba_from_sample};
+
+// Frames of recorded files, pulled from a decoding pipeline through an appsink
+// and encoded in memory: single thumbnails, from a paused pipeline that seeks
+// to the frame, and scrubbing previews of evenly spaced frames, taken in one
+// decoding pass through the file however many frames are asked for.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SeekMode {
+    // Nearest keyframe: fast, but up to a keyframe interval off
+    KeyUnit,
+    // Decodes from the previous keyframe up to the exact position
+    Accurate,
+}
+
+impl SeekMode {
+    fn flags(&self) -> SeekFlags {
+        match self {
+            SeekMode::KeyUnit => SeekFlags::FLUSH | SeekFlags::KEY_UNIT | SeekFlags::SNAP_NEAREST,
+            SeekMode::Accurate => SeekFlags::FLUSH | SeekFlags::ACCURATE,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ThumbnailFormat {
+    Jpeg,
+    Png,
+}
+
+#[derive(Debug, Clone)]
+pub struct VideoThumbnailOptions {
+    pub position_ms: u64,
+    // Width follows the video's aspect ratio
+    pub height: u32,
+    pub seek: SeekMode,
+    pub format: ThumbnailFormat,
+    // Ignored for Png
+    pub jpeg_quality: u8,
+    // Limit for each preroll (opening the file, then the seek)
+    pub timeout_ms: u32,
+}
+
+impl Default for VideoThumbnailOptions {
+    fn default() -> Self {
+        Self {
+            position_ms: 0,
+            height: 360,
+            seek: SeekMode::KeyUnit,
+            format: ThumbnailFormat::Jpeg,
+            jpeg_quality: 80,
+            timeout_ms: 5_000,
+        }
+    }
+}
+
+pub struct VideoThumbnail {
+    pub width: u32,
+    pub height: u32,
+    // Position of the decoded frame; with KeyUnit the keyframe's
+    pub position_ms: u64,
+    pub format: ThumbnailFormat,
+    // Encoded image, ready for `Image.memory`
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ThumbnailStripOptions {
+    // Number of frames, spread evenly over the duration
+    pub count: u32,
+    // Height of each frame; width follows the video's aspect ratio
+    pub tile_height: u32,
+    // Frames per sprite sheet row
+    pub columns: u32,
+    pub jpeg_quality: u8,
+    // How the WebVTT cues refer to the sprite sheet, e.g. its file name next to the .vtt
+    pub sprite_url: String,
+}
+
+impl Default for ThumbnailStripOptions {
+    fn default() -> Self {
+        Self {
+            count: 20,
+            tile_height: 90,
+            columns: 5,
+            jpeg_quality: 75,
+            sprite_url: "sprite.jpg".to_string(),
+        }
+    }
+}
+
+pub struct StripFrame {
+    // Position of the decoded frame: the first at or after the middle of its
+    // slice of the timeline, or the last frame if the video ends before that
+    pub position_ms: u64,
+    pub jpeg: Vec<u8>,
+}
+
+pub struct ThumbnailStrip {
+    pub duration_ms: u64,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    pub columns: u32,
+    pub rows: u32,
+    pub frames: Vec<StripFrame>,
+    // Every frame in one JPEG, left to right then top to bottom
+    pub sprite_jpeg: Vec<u8>,
+    // WebVTT index: one cue per frame covering its share of the timeline,
+    // pointing at its tile as `<sprite_url>#xywh=x,y,w,h`
+    pub vtt: String,
+}
+
+pub fn default_thumbnail_strip_options() -> ThumbnailStripOptions {
+    ThumbnailStripOptions::default()
+}
+
+pub fn default_video_thumbnail_options() -> VideoThumbnailOptions {
+    VideoThumbnailOptions::default()
+}
+
+const PREROLL_TIMEOUT_MS: u64 = 5_000;
+
+// filesrc ! decodebin ! videoscale ! videoconvert ! appsink, negotiating RGBA
+// at `height` with square pixels. Scaling first keeps the conversion cheap when
+// every frame of a file goes through. Left in Null.
+fn build_frame_pipeline(video_path: &str, height: u32) -> Result<(Pipeline, AppSink)> {
+    gstreamer::init().map_err(|e| anyhow!("Failed to init GStreamer: {}", e))?;
+    if !Path::new(video_path).is_file() {
+        return Err(anyhow!("Video {} not found", video_path));
+    }
+    let pipeline = Pipeline::new();
+    let src = ElementFactory::make("filesrc").property("location", video_path).build()?;
+    let decode = ElementFactory::make("decodebin").build()?;
+    let convert = ElementFactory::make("videoconvert").build()?;
+    let scale = ElementFactory::make("videoscale").build()?;
+    let caps = Caps::builder("video/x-raw")
+        .field("format", "RGBA")
+        .field("height", height as i32)
+        .field("pixel-aspect-ratio", gstreamer::Fraction::new(1, 1))
+        .build();
+    // A few frames ahead of the puller, so decoding a whole file doesn't queue it all
+    let sink = AppSink::builder().caps(&caps).sync(false).max_buffers(4).build();
+    pipeline.add_many([&src, &decode, &scale, &convert, sink.upcast_ref::<Element>()])?;
+    src.link(&decode)?;
+    Element::link_many([&scale, &convert, sink.upcast_ref::<Element>()])?;
+
+    // decodebin exposes its pads once it has typefound the file
+    let scale_sink = scale.static_pad("sink").ok_or_else(|| anyhow!("videoscale has no sink pad"))?;
+    decode.connect_pad_added(move |_, pad| {
+        let is_video = pad.current_caps()
+            .and_then(|caps| caps.structure(0).map(|s| s.name().starts_with("video/")))
+            .unwrap_or(false);
+        if is_video && !scale_sink.is_linked() {
+            let _ = pad.link(&scale_sink);
+        }
+    });
+    Ok((pipeline, sink))
+}
+
+// Waits for a paused pipeline to preroll, after the state change or a flushing
+// seek. Surfaces the decoder's error instead of timing out on it.
+fn wait_for_preroll(pipeline: &Pipeline, timeout_ms: u64) -> Result<()> {
+    let bus = pipeline.bus().ok_or_else(|| anyhow!("Pipeline has no bus"))?;
+    let deadline = std::time::Instant::now() + std::time::Duration::from_millis(timeout_ms);
+    loop {
+        let left = deadline.saturating_duration_since(std::time::Instant::now());
+        if left.is_zero() {
+            return Err(anyhow!("Video did not preroll within {}ms", timeout_ms));
+        }
+        let msg = bus.timed_pop_filtered(
+            ClockTime::from_mseconds(left.as_millis() as u64),
+            &[MessageType::AsyncDone, MessageType::Error, MessageType::Eos],
+        );
+        match msg.as_ref().map(|msg| msg.view()) {
+            Some(MessageView::AsyncDone(..)) => return Ok(()),
+            Some(MessageView::Error(err)) => return Err(anyhow!("Decoding failed: {}", err.error())),
+            Some(MessageView::Eos(..)) => return Err(anyhow!("Reached the end of the video")),
+            _ => (),
+        }
+    }
+}
+
+// Flushing seek on a paused pipeline, then the frame it prerolls at
+fn seek_and_pull(pipeline: &Pipeline, sink: &AppSink, target: ClockTime, seek: SeekMode, timeout_ms: u64) -> Result<gstreamer::Sample> {
+    pipeline.seek_simple(seek.flags(), target)?;
+    wait_for_preroll(pipeline, timeout_ms)?;
+    sink.try_pull_preroll(ClockTime::from_mseconds(timeout_ms))
+        .ok_or_else(|| anyhow!("No frame at {}ms", target.mseconds()))
+}
+
+fn encode(width: u32, height: u32, rgba: Vec<u8>, format: ThumbnailFormat, jpeg_quality: u8) -> Result<Vec<u8>> {
+    match format {
+        ThumbnailFormat::Jpeg => encode_jpeg(width, height, rgba, jpeg_quality),
+        ThumbnailFormat::Png => {
+            let mut png = Vec::new();
+            image::codecs::png::PngEncoder::new(&mut png)
+                .write_image(&rgba, width, height, image::ColorType::Rgba8)?;
+            Ok(png)
+        }
+    }
+}
+
+// One frame of a recorded file, encoded in memory. Waits for each preroll
+// rather than for EOS, so it returns as soon as the frame is decoded.
+pub fn render_video_thumbnail(video_path: String, options: VideoThumbnailOptions) -> Result<VideoThumbnail> {
+    if !(16..=2160).contains(&options.height) {
+        return Err(anyhow!("Thumbnail height must be between 16 and 2160, got {}", options.height));
+    }
+    let timeout_ms = options.timeout_ms as u64;
+    let (pipeline, sink) = build_frame_pipeline(&video_path, options.height)?;
+    let rendered = (|| {
+        pipeline.set_state(State::Paused)?;
+        wait_for_preroll(&pipeline, timeout_ms)?;
+        let target = ClockTime::from_mseconds(options.position_ms);
+        if let Some(duration) = pipeline.query_duration::<ClockTime>() {
+            if target >= duration {
+                return Err(anyhow!("Position {}ms is past the end of the video ({}ms)", options.position_ms, duration.mseconds()));
+            }
+        }
+        seek_and_pull(&pipeline, &sink, target, options.seek, timeout_ms)
+    })();
+    let _ = pipeline.set_state(State::Null);
+    let sample = rendered?;
+
+    let position_ms = sample.buffer().and_then(|buffer| buffer.pts()).map(|pts| pts.mseconds()).unwrap_or(options.position_ms);
+    let (width, height, rgba) = rgba_from_sample(&sample)?;
+    Ok(VideoThumbnail {
+        width,
+        height,
+        position_ms,
+        format: options.format,
+        bytes: encode(width, height, rgba, options.format, options.jpeg_quality)?,
+    })
+}
+
+fn vtt_time(ms: u64) -> String {
+    format!("{:02}:{:02}:{:02}.{:03}", ms / 3_600_000, ms / 60_000 % 60, ms / 1000 % 60, ms % 1000)
+}
+
+// Middle of each of `count` equal slices of the timeline, so neither the first
+// nor the last frame is a fade-in or falls past the end
+fn frame_targets(duration: ClockTime, count: u32) -> Vec<ClockTime> {
+    let count = count as u64;
+    (0..count)
+        .map(|i| ClockTime::from_nseconds(duration.nseconds() * (2 * i + 1) / (2 * count)))
+        .collect()
+}
+
+// Top-left corner of tile `index` on a sheet `columns` tiles wide
+fn tile_origin(index: u32, columns: u32, (tile_width, tile_height): (u32, u32)) -> (u32, u32) {
+    ((index % columns) * tile_width, (index / columns) * tile_height)
+}
+
+// WebVTT index of `count` tiles, each covering an equal share of `duration_ms`
+fn build_vtt(duration_ms: u64, count: u32, columns: u32, tile: (u32, u32), sprite_url: &str) -> String {
+    let mut vtt = String::from("WEBVTT\n");
+    for i in 0..count {
+        let (x, y) = tile_origin(i, columns, tile);
+        let start = duration_ms * i as u64 / count as u64;
+        let end = duration_ms * (i as u64 + 1) / count as u64;
+        vtt.push_str(&format!(
+            "\n{} --> {}\n{}#xywh={},{},{},{}\n",
+            vtt_time(start), vtt_time(end), sprite_url, x, y, tile.0, tile.1
+        ));
+    }
+    vtt
+}
+
+fn sample_tile(sample: &gstreamer::Sample) -> Result<image::RgbaImage> {
+    let (width, height, rgba) = rgba_from_sample(sample)?;
+    image::RgbaImage::from_raw(width, height, rgba).ok_or_else(|| anyhow!("Frame size mismatch"))
+}
+
+// Plays the file through once, decoding every frame, and keeps the first frame
+// at or after each target. Targets past the last frame get the last frame.
+// Seeking per frame would skip decoding, but costs a flush and preroll each.
+fn extract_frames(pipeline: &Pipeline, sink: &AppSink, count: u32) -> Result<(u64, Vec<(u64, image::RgbaImage)>)> {
+    pipeline.set_state(State::Paused)?;
+    wait_for_preroll(pipeline, PREROLL_TIMEOUT_MS)?;
+    let duration = pipeline.query_duration::<ClockTime>()
+        .ok_or_else(|| anyhow!("Video has no known duration"))?;
+    let targets = frame_targets(duration, count);
+    pipeline.set_state(State::Playing)?;
+
+    let mut frames = Vec::with_capacity(count as usize);
+    let mut last = None;
+    while frames.len() < targets.len() {
+        let Some(sample) = sink.try_pull_sample(ClockTime::from_mseconds(PREROLL_TIMEOUT_MS)) else {
+            if sink.is_eos() {
+                break;
+            }
+            let bus = pipeline.bus().ok_or_else(|| anyhow!("Pipeline has no bus"))?;
+            if let Some(msg) = bus.pop_filtered(&[MessageType::Error]) {
+                if let MessageView::Error(err) = msg.view() {
+                    return Err(anyhow!("Decoding failed: {}", err.error()));
+                }
+            }
+            return Err(anyhow!("No frame within {}ms", PREROLL_TIMEOUT_MS));
+        };
+        let Some(pts) = sample.buffer().and_then(|buffer| buffer.pts()) else { continue };
+        // A frame can stand in for several targets in a video with few frames
+        if pts >= targets[frames.len()] {
+            let tile = sample_tile(&sample)?;
+            while frames.len() < targets.len() && pts >= targets[frames.len()] {
+                frames.push((pts.mseconds(), tile.clone()));
+            }
+        }
+        last = Some((pts, sample));
+    }
+    if frames.len() < targets.len() {
+        let (pts, sample) = last.ok_or_else(|| anyhow!("Video has no frames"))?;
+        let tile = sample_tile(&sample)?;
+        frames.resize(targets.len(), (pts.mseconds(), tile));
+    }
+    Ok((duration.mseconds(), frames))
+}
+
+// Extracts `options.count` frames of a recorded file as individual JPEGs, a
+// sprite sheet and its WebVTT index for the dashboard's scrub bar
+pub fn extract_thumbnail_strip(video_path: String, options: ThumbnailStripOptions) -> Result<ThumbnailStrip> {
+    if !(1..=200).contains(&options.count) {
+        return Err(anyhow!("Frame count must be between 1 and 200, got {}", options.count));
+    }
+    if !(16..=720).contains(&options.tile_height) {
+        return Err(anyhow!("Tile height must be between 16 and 720, got {}", options.tile_height));
+    }
+    if options.columns == 0 {
+        return Err(anyhow!("A sprite sheet needs at least one column"));
+    }
+
+    let (pipeline, sink) = build_frame_pipeline(&video_path, options.tile_height)?;
+    let extracted = extract_frames(&pipeline, &sink, options.count);
+    let _ = pipeline.set_state(State::Null);
+    let (duration_ms, tiles) = extracted?;
+
+    let (tile_width, tile_height) = tiles[0].1.dimensions();
+    let columns = options.columns.min(options.count);
+    let rows = options.count.div_ceil(columns);
+    let mut sheet = image::RgbaImage::new(tile_width * columns, tile_height * rows);
+    let mut frames = Vec::with_capacity(tiles.len());
+    for (i, (position_ms, tile)) in tiles.into_iter().enumerate() {
+        let (x, y) = tile_origin(i as u32, columns, (tile_width, tile_height));
+        image::imageops::replace(&mut sheet, &tile, x as i64, y as i64);
+
+        let (width, height) = tile.dimensions();
+        frames.push(StripFrame {
+            position_ms,
+            jpeg: encode_jpeg(width, height, tile.into_raw(), options.jpeg_quality)?,
+        });
+    }
+
+    let (sheet_width, sheet_height) = sheet.dimensions();
+    Ok(ThumbnailStrip {
+        duration_ms,
+        tile_width,
+        tile_height,
+        columns,
+        rows,
+        frames,
+        sprite_jpeg: encode_jpeg(sheet_width, sheet_height, sheet.into_raw(), options.jpeg_quality)?,
+        vtt: build_vtt(duration_ms, options.count, columns, (tile_width, tile_height), &options.sprite_url),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_vtt_timestamps() {
+        assert_eq!(vtt_time(0), "00:00:00.000");
+        assert_eq!(vtt_time(999), "00:00:00.999");
+        assert_eq!(vtt_time(61_005), "00:01:01.005");
+        assert_eq!(vtt_time(3_599_999), "00:59:59.999");
+        assert_eq!(vtt_time(36_000_000 + 120_000 + 3_042), "10:02:03.042");
+    }
+
+    #[test]
+    fn targets_middle_of_each_slice() {
+        let targets = frame_targets(ClockTime::from_seconds(10), 4);
+        let ms: Vec<u64> = targets.iter().map(|t| t.mseconds()).collect();
+        assert_eq!(ms, [1_250, 3_750, 6_250, 8_750]);
+    }
+
+    #[test]
+    fn lays_tiles_out_in_rows() {
+        let tile = (160, 90);
+        assert_eq!(tile_origin(0, 3, tile), (0, 0));
+        assert_eq!(tile_origin(2, 3, tile), (320, 0));
+        assert_eq!(tile_origin(3, 3, tile), (0, 90));
+        assert_eq!(tile_origin(7, 3, tile), (160, 180));
+        assert_eq!(tile_origin(4, 1, tile), (0, 360));
+    }
+
+    #[test]
+    fn indexes_sprite_tiles_in_vtt() {
+        let vtt = build_vtt(10_000, 5, 2, (160, 90), "sprite.jpg");
+        assert_eq!(vtt, "WEBVTT\n\
+            \n00:00:00.000 --> 00:00:02.000\nsprite.jpg#xywh=0,0,160,90\n\
+            \n00:00:02.000 --> 00:00:04.000\nsprite.jpg#xywh=160,0,160,90\n\
+            \n00:00:04.000 --> 00:00:06.000\nsprite.jpg#xywh=0,90,160,90\n\
+            \n00:00:06.000 --> 00:00:08.000\nsprite.jpg#xywh=160,90,160,90\n\
+            \n00:00:08.000 --> 00:00:10.000\nsprite.jpg#xywh=0,180,160,90\n");
+    }
+
+    #[test]
+    fn vtt_cues_cover_the_whole_timeline() {
+        // 1001ms over 3 cues doesn't divide evenly; cues still meet and end at the duration
+        let vtt = build_vtt(1_001, 3, 3, (16, 16), "s.jpg");
+        let cues: Vec<&str> = vtt.lines().filter(|line| line.contains("-->")).collect();
+        assert_eq!(cues, ["00:00:00.000 --> 00:00:00.333", "00:00:00.333 --> 00:00:00.667", "00:00:00.667 --> 00:00:01.001"]);
+    }
+}
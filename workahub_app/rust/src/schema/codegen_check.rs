@@ -0,0 +1,65 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// Regenerates the FlatBuffers bindings from schema/monitoring.fbs into a temp
+// dir with `flatc` (or $FLATC) and compares them with the checked-in copies
+// that schema/generate.sh writes:
+//   src/schema/monitoring_generated.rs
+//   ../lib/src/flatbuffers/monitoring_workahub.monitoring_generated.dart
+// Needs flatc, so it is ignored by default; CI installs it and runs
+//   cargo test -- --ignored generated_bindings_match_schema
+
+const SCHEMA: &str = "../schema/monitoring.fbs";
+const RUST_OUT: &str = "src/schema/monitoring_generated.rs";
+const DART_OUT: &str = "../lib/src/flatbuffers/monitoring_workahub.monitoring_generated.dart";
+// Same version schema/generate.sh insists on
+const FLATC_VERSION: &str = "25.12.19";
+
+fn manifest_path(relative: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join(relative)
+}
+
+fn normalize(s: &str) -> String {
+    s.replace("\r\n", "\n").trim_end().to_string()
+}
+
+#[test]
+#[ignore = "needs flatc 25.12.19"]
+fn generated_bindings_match_schema() {
+    let flatc = env::var("FLATC").unwrap_or_else(|_| "flatc".to_string());
+    let out = Command::new(&flatc).arg("--version").output()
+        .unwrap_or_else(|e| panic!("{} not found ({}); install flatc {} or set $FLATC", flatc, e, FLATC_VERSION));
+    let version = String::from_utf8_lossy(&out.stdout);
+    assert!(version.contains(FLATC_VERSION), "{} is {}, expected {}", flatc, version.trim(), FLATC_VERSION);
+
+    let out_dir = env::temp_dir().join(format!("workahub-flatc-{}", std::process::id()));
+    fs::create_dir_all(&out_dir).unwrap();
+
+    let mut stale = Vec::new();
+    for (lang, checked_in) in [("--rust", RUST_OUT), ("--dart", DART_OUT)] {
+        let status = Command::new(&flatc)
+            .args([lang, "-o"])
+            .arg(&out_dir)
+            .arg(manifest_path(SCHEMA))
+            .status()
+            .unwrap_or_else(|e| panic!("Failed to run {}: {}", flatc, e));
+        assert!(status.success(), "{} {} failed on {}: {}", flatc, lang, SCHEMA, status);
+
+        let checked_in = manifest_path(checked_in);
+        let fresh = fs::read_to_string(out_dir.join(checked_in.file_name().unwrap())).unwrap();
+        let current = fs::read_to_string(&checked_in).unwrap_or_default();
+        if normalize(&current) != normalize(&fresh) {
+            stale.push(checked_in.display().to_string());
+        }
+    }
+    let _ = fs::remove_dir_all(&out_dir);
+
+    assert!(
+        stale.is_empty(),
+        "{} out of date with {}. Run schema/generate.sh and commit the result.",
+        stale.join(", "),
+        SCHEMA
+    );
+}
@@ -0,0 +1,302 @@
+use std::fmt;
+use flatbuffers::{InvalidFlatbuffer, VerifierOptions, FILE_IDENTIFIER_LENGTH, SIZE_UOFFSET};
+use crate::api::monitor::{InputStats, SystemStats};
+use crate::schema::MONITORING_SCHEMA_VERSION;
+use crate::schema::monitoring_generated::workahub::monitoring::{
+    monitoring_packet_size_prefixed_buffer_has_identifier,
+    size_prefixed_root_as_monitoring_packet_with_opts, MonitoringPacket,
+};
+
+// Errors returned when a monitoring buffer cannot be trusted. Everything that
+// reads packets produced by `get_monitoring_packet_fbs` goes through here.
+#[derive(Debug)]
+pub enum DecodeError {
+    // Fewer bytes than the size prefix (or the prefix itself) promises.
+    Truncated { needed: usize, available: usize },
+    // Size prefix larger than any real packet, usually a corrupt stream.
+    FrameTooLarge(usize),
+    // More bytes than a single frame; use `PacketFrames` for concatenated packets.
+    TrailingBytes(usize),
+    MissingIdentifier,
+    Invalid(InvalidFlatbuffer),
+    MissingField(&'static str),
+    // Written by a newer schema than this build understands.
+    UnsupportedVersion(u16),
+    Io(std::io::Error),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Truncated { needed, available } => {
+                write!(f, "Truncated packet: need {} bytes, have {}", needed, available)
+            }
+            DecodeError::FrameTooLarge(n) => {
+                write!(f, "Frame of {} bytes exceeds the {} byte limit", n, MAX_FRAME_LEN)
+            }
+            DecodeError::TrailingBytes(n) => write!(f, "{} trailing bytes after packet", n),
+            DecodeError::MissingIdentifier => write!(f, "Buffer is missing the monitoring packet identifier"),
+            DecodeError::Invalid(e) => write!(f, "Invalid monitoring packet: {}", e),
+            DecodeError::MissingField(name) => write!(f, "Monitoring packet has no {} table", name),
+            DecodeError::UnsupportedVersion(v) => write!(
+                f, "Unsupported schema version {} (max {})", v, MONITORING_SCHEMA_VERSION
+            ),
+            DecodeError::Io(e) => write!(f, "Failed to read packet stream: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DecodeError::Invalid(e) => Some(e),
+            DecodeError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<InvalidFlatbuffer> for DecodeError {
+    fn from(e: InvalidFlatbuffer) -> Self {
+        DecodeError::Invalid(e)
+    }
+}
+
+impl From<std::io::Error> for DecodeError {
+    fn from(e: std::io::Error) -> Self {
+        DecodeError::Io(e)
+    }
+}
+
+// Owned copy of a verified `MonitoringPacket`, detached from its buffer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DecodedPacket {
+    pub input: InputStats,
+    pub system: SystemStats,
+    pub timestamp: i64,
+    pub schema_version: u16,
+}
+
+impl TryFrom<MonitoringPacket<'_>> for DecodedPacket {
+    type Error = DecodeError;
+
+    fn try_from(packet: MonitoringPacket<'_>) -> Result<Self, Self::Error> {
+        let schema_version = packet.schema_version();
+        if schema_version > MONITORING_SCHEMA_VERSION {
+            return Err(DecodeError::UnsupportedVersion(schema_version));
+        }
+        let input = packet.input().ok_or(DecodeError::MissingField("input"))?;
+        let system = packet.system().ok_or(DecodeError::MissingField("system"))?;
+
+        Ok(DecodedPacket {
+            input: InputStats {
+                mouse_clicks: input.mouse_clicks(),
+                key_presses: input.key_presses(),
+                mouse_moves: input.mouse_moves(),
+            },
+            system: SystemStats {
+                cpu_usage: system.cpu_usage(),
+                memory_used: system.memory_used(),
+                memory_total: system.memory_total(),
+            },
+            timestamp: packet.timestamp(),
+            schema_version,
+        })
+    }
+}
+
+// A packet is a few hundred bytes; this only guards against allocating for a corrupt prefix.
+pub const MAX_FRAME_LEN: usize = 64 * 1024;
+
+// Packets are three small tables; anything deeper or larger is garbage.
+fn verifier_options() -> VerifierOptions {
+    VerifierOptions {
+        max_depth: 8,
+        max_tables: 16,
+        ..Default::default()
+    }
+}
+
+// Length of the size-prefixed frame at the start of `buf`, prefix included.
+pub fn frame_len(buf: &[u8]) -> Result<usize, DecodeError> {
+    if buf.len() < SIZE_UOFFSET {
+        return Err(DecodeError::Truncated { needed: SIZE_UOFFSET, available: buf.len() });
+    }
+    let len = u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(DecodeError::FrameTooLarge(len));
+    }
+    let needed = SIZE_UOFFSET + len;
+    if buf.len() < needed {
+        return Err(DecodeError::Truncated { needed, available: buf.len() });
+    }
+    Ok(needed)
+}
+
+// Checks the identifier and runs the flatbuffers verifier over exactly one frame.
+pub fn verify_frame(frame: &[u8]) -> Result<MonitoringPacket<'_>, DecodeError> {
+    let len = frame_len(frame)?;
+    if len != frame.len() {
+        return Err(DecodeError::TrailingBytes(frame.len() - len));
+    }
+    // has_identifier asserts rather than failing on frames too short to hold one
+    if frame.len() < SIZE_UOFFSET + SIZE_UOFFSET + FILE_IDENTIFIER_LENGTH
+        || !monitoring_packet_size_prefixed_buffer_has_identifier(frame)
+    {
+        return Err(DecodeError::MissingIdentifier);
+    }
+    Ok(size_prefixed_root_as_monitoring_packet_with_opts(&verifier_options(), frame)?)
+}
+
+// Verifies a buffer from `get_monitoring_packet_fbs` and copies it into plain structs.
+pub fn decode_packet(buf: &[u8]) -> Result<DecodedPacket, DecodeError> {
+    DecodedPacket::try_from(verify_frame(buf)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::monitor::encode_monitoring_packet;
+    use crate::schema::monitoring_generated::workahub::monitoring::{
+        finish_size_prefixed_monitoring_packet_buffer, InputStats as FbsInputStats, InputStatsArgs,
+        MonitoringPacketArgs, SystemStats as FbsSystemStats, SystemStatsArgs,
+    };
+    use crate::schema::stream::{PacketFrames, PacketStreamReader};
+    use flatbuffers::FlatBufferBuilder;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn stats() -> (InputStats, SystemStats) {
+        (
+            InputStats { mouse_clicks: 12, key_presses: 345, mouse_moves: 6789 },
+            SystemStats { cpu_usage: 37.5, memory_used: 8 << 30, memory_total: 16 << 30 },
+        )
+    }
+
+    fn packet_with_version(schema_version: u16) -> Vec<u8> {
+        let mut builder = FlatBufferBuilder::new();
+        let input = FbsInputStats::create(&mut builder, &InputStatsArgs { mouse_clicks: 1, key_presses: 2, mouse_moves: 3 });
+        let system = FbsSystemStats::create(&mut builder, &SystemStatsArgs { cpu_usage: 1.0, memory_used: 2, memory_total: 3 });
+        let packet = MonitoringPacket::create(&mut builder, &MonitoringPacketArgs {
+            input: Some(input),
+            system: Some(system),
+            timestamp: 42,
+            schema_version,
+        });
+        finish_size_prefixed_monitoring_packet_buffer(&mut builder, packet);
+        builder.finished_data().to_vec()
+    }
+
+    #[test]
+    fn round_trips_encoded_stats() {
+        let (input, system) = stats();
+        let buf = encode_monitoring_packet(&input, &system, 1_700_000_000_000);
+        let decoded = decode_packet(&buf).unwrap();
+        assert_eq!(decoded, DecodedPacket {
+            input,
+            system,
+            timestamp: 1_700_000_000_000,
+            schema_version: MONITORING_SCHEMA_VERSION,
+        });
+    }
+
+    #[test]
+    fn decodes_live_packet() {
+        let decoded = decode_packet(&crate::api::monitor::get_monitoring_packet_fbs()).unwrap();
+        assert_eq!(decoded.schema_version, MONITORING_SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn rejects_truncated_frames() {
+        let (input, system) = stats();
+        let buf = encode_monitoring_packet(&input, &system, 0);
+        assert!(matches!(
+            decode_packet(&buf[..buf.len() - 1]),
+            Err(DecodeError::Truncated { needed, available }) if needed == buf.len() && available == buf.len() - 1
+        ));
+        assert!(matches!(
+            decode_packet(&buf[..2]),
+            Err(DecodeError::Truncated { needed: SIZE_UOFFSET, available: 2 })
+        ));
+    }
+
+    #[test]
+    fn rejects_trailing_bytes() {
+        let (input, system) = stats();
+        let mut buf = encode_monitoring_packet(&input, &system, 0);
+        buf.extend_from_slice(&[0, 0, 0]);
+        assert!(matches!(decode_packet(&buf), Err(DecodeError::TrailingBytes(3))));
+    }
+
+    #[test]
+    fn rejects_wrong_identifier() {
+        let (input, system) = stats();
+        let mut buf = encode_monitoring_packet(&input, &system, 0);
+        // size prefix, root offset, then the 4 identifier bytes
+        buf[SIZE_UOFFSET + 4..SIZE_UOFFSET + 8].copy_from_slice(b"XXXX");
+        assert!(matches!(decode_packet(&buf), Err(DecodeError::MissingIdentifier)));
+    }
+
+    #[test]
+    fn rejects_frames_too_short_for_an_identifier() {
+        assert!(matches!(decode_packet(&[4, 0, 0, 0, 0, 0, 0, 0]), Err(DecodeError::MissingIdentifier)));
+    }
+
+    #[test]
+    fn rejects_oversized_prefix() {
+        let len = (MAX_FRAME_LEN as u32 + 1).to_le_bytes();
+        assert!(matches!(decode_packet(&len), Err(DecodeError::FrameTooLarge(n)) if n == MAX_FRAME_LEN + 1));
+    }
+
+    #[test]
+    fn rejects_newer_schema_version() {
+        let buf = packet_with_version(MONITORING_SCHEMA_VERSION + 1);
+        assert!(matches!(
+            decode_packet(&buf),
+            Err(DecodeError::UnsupportedVersion(v)) if v == MONITORING_SCHEMA_VERSION + 1
+        ));
+        assert!(decode_packet(&packet_with_version(MONITORING_SCHEMA_VERSION)).is_ok());
+    }
+
+    // Every reader must reject garbage with an error, never a panic
+    fn read_all(buf: &[u8]) {
+        let _ = decode_packet(buf);
+        for packet in PacketFrames::new(buf).flatten() {
+            let _ = DecodedPacket::try_from(packet);
+        }
+        for frame in PacketStreamReader::new(std::io::Cursor::new(buf)).flatten() {
+            let _ = decode_packet(&frame);
+        }
+    }
+
+    #[test]
+    fn survives_random_buffers() {
+        let mut rng = StdRng::seed_from_u64(0x5748_4d50);
+        for _ in 0..5_000 {
+            let mut buf = vec![0u8; rng.random_range(0..256)];
+            rng.fill(&mut buf[..]);
+            // Plausible size prefixes reach the verifier instead of failing on length
+            if buf.len() >= SIZE_UOFFSET && rng.random_bool(0.5) {
+                let len = (buf.len() - SIZE_UOFFSET) as u32;
+                buf[..SIZE_UOFFSET].copy_from_slice(&len.to_le_bytes());
+                if buf.len() >= SIZE_UOFFSET + 8 {
+                    buf[SIZE_UOFFSET + 4..SIZE_UOFFSET + 8].copy_from_slice(b"WHMP");
+                }
+            }
+            read_all(&buf);
+        }
+    }
+
+    #[test]
+    fn survives_bit_flips() {
+        let (input, system) = stats();
+        let valid = encode_monitoring_packet(&input, &system, 0);
+        let mut stream = valid.clone();
+        stream.extend_from_slice(&valid);
+        for bit in 0..stream.len() * 8 {
+            let mut buf = stream.clone();
+            buf[bit / 8] ^= 1 << (bit % 8);
+            read_all(&buf);
+        }
+    }
+}
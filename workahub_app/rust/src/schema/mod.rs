@@ -1,2 +1,9 @@
 #[allow(dead_code, unused_imports)]
 pub mod monitoring_generated;
+pub mod decode;
+pub mod stream;
+#[cfg(test)]
+mod codegen_check;
+
+// Matches the default of `MonitoringPacket.schema_version` in schema/monitoring.fbs.
+pub const MONITORING_SCHEMA_VERSION: u16 = 1;
@@ -289,6 +289,7 @@ impl<'a> MonitoringPacket<'a> {
   pub const VT_INPUT: ::flatbuffers::VOffsetT = 4;
   pub const VT_SYSTEM: ::flatbuffers::VOffsetT = 6;
   pub const VT_TIMESTAMP: ::flatbuffers::VOffsetT = 8;
+  pub const VT_SCHEMA_VERSION: ::flatbuffers::VOffsetT = 10;
 
   #[inline]
   pub unsafe fn init_from_table(table: ::flatbuffers::Table<'a>) -> Self {
@@ -303,6 +304,7 @@ impl<'a> MonitoringPacket<'a> {
     builder.add_timestamp(args.timestamp);
     if let Some(x) = args.system { builder.add_system(x); }
     if let Some(x) = args.input { builder.add_input(x); }
+    builder.add_schema_version(args.schema_version);
     builder.finish()
   }
 
@@ -328,6 +330,13 @@ impl<'a> MonitoringPacket<'a> {
     // which contains a valid value in this slot
     unsafe { self._tab.get::<i64>(MonitoringPacket::VT_TIMESTAMP, Some(0)).unwrap()}
   }
+  #[inline]
+  pub fn schema_version(&self) -> u16 {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<u16>(MonitoringPacket::VT_SCHEMA_VERSION, Some(1)).unwrap()}
+  }
 }
 
 impl ::flatbuffers::Verifiable for MonitoringPacket<'_> {
@@ -339,6 +348,7 @@ impl ::flatbuffers::Verifiable for MonitoringPacket<'_> {
      .visit_field::<::flatbuffers::ForwardsUOffset<InputStats>>("input", Self::VT_INPUT, false)?
      .visit_field::<::flatbuffers::ForwardsUOffset<SystemStats>>("system", Self::VT_SYSTEM, false)?
      .visit_field::<i64>("timestamp", Self::VT_TIMESTAMP, false)?
+     .visit_field::<u16>("schema_version", Self::VT_SCHEMA_VERSION, false)?
      .finish();
     Ok(())
   }
@@ -347,6 +357,7 @@ pub struct MonitoringPacketArgs<'a> {
     pub input: Option<::flatbuffers::WIPOffset<InputStats<'a>>>,
     pub system: Option<::flatbuffers::WIPOffset<SystemStats<'a>>>,
     pub timestamp: i64,
+    pub schema_version: u16,
 }
 impl<'a> Default for MonitoringPacketArgs<'a> {
   #[inline]
@@ -355,6 +366,7 @@ impl<'a> Default for MonitoringPacketArgs<'a> {
       input: None,
       system: None,
       timestamp: 0,
+      schema_version: 1,
     }
   }
 }
@@ -377,6 +389,10 @@ impl<'a: 'b, 'b, A: ::flatbuffers::Allocator + 'a> MonitoringPacketBuilder<'a, '
     self.fbb_.push_slot::<i64>(MonitoringPacket::VT_TIMESTAMP, timestamp, 0);
   }
   #[inline]
+  pub fn add_schema_version(&mut self, schema_version: u16) {
+    self.fbb_.push_slot::<u16>(MonitoringPacket::VT_SCHEMA_VERSION, schema_version, 1);
+  }
+  #[inline]
   pub fn new(_fbb: &'b mut ::flatbuffers::FlatBufferBuilder<'a, A>) -> MonitoringPacketBuilder<'a, 'b, A> {
     let start = _fbb.start_table();
     MonitoringPacketBuilder {
@@ -397,6 +413,7 @@ impl ::core::fmt::Debug for MonitoringPacket<'_> {
       ds.field("input", &self.input());
       ds.field("system", &self.system());
       ds.field("timestamp", &self.timestamp());
+      ds.field("schema_version", &self.schema_version());
       ds.finish()
   }
 }
@@ -460,16 +477,30 @@ pub unsafe fn root_as_monitoring_packet_unchecked(buf: &[u8]) -> MonitoringPacke
 pub unsafe fn size_prefixed_root_as_monitoring_packet_unchecked(buf: &[u8]) -> MonitoringPacket<'_> {
   unsafe { ::flatbuffers::size_prefixed_root_unchecked::<MonitoringPacket>(buf) }
 }
+pub const MONITORING_PACKET_IDENTIFIER: &str = "WHMP";
+
+#[inline]
+pub fn monitoring_packet_buffer_has_identifier(buf: &[u8]) -> bool {
+  ::flatbuffers::buffer_has_identifier(buf, MONITORING_PACKET_IDENTIFIER, false)
+}
+
+#[inline]
+pub fn monitoring_packet_size_prefixed_buffer_has_identifier(buf: &[u8]) -> bool {
+  ::flatbuffers::buffer_has_identifier(buf, MONITORING_PACKET_IDENTIFIER, true)
+}
+
+pub const MONITORING_PACKET_EXTENSION: &str = "whmp";
+
 #[inline]
 pub fn finish_monitoring_packet_buffer<'a, 'b, A: ::flatbuffers::Allocator + 'a>(
     fbb: &'b mut ::flatbuffers::FlatBufferBuilder<'a, A>,
     root: ::flatbuffers::WIPOffset<MonitoringPacket<'a>>) {
-  fbb.finish(root, None);
+  fbb.finish(root, Some(MONITORING_PACKET_IDENTIFIER));
 }
 
 #[inline]
 pub fn finish_size_prefixed_monitoring_packet_buffer<'a, 'b, A: ::flatbuffers::Allocator + 'a>(fbb: &'b mut ::flatbuffers::FlatBufferBuilder<'a, A>, root: ::flatbuffers::WIPOffset<MonitoringPacket<'a>>) {
-  fbb.finish_size_prefixed(root, None);
+  fbb.finish_size_prefixed(root, Some(MONITORING_PACKET_IDENTIFIER));
 }
 }  // pub mod Monitoring
 }  // pub mod Workahub
@@ -0,0 +1,192 @@
+use flatbuffers::SIZE_UOFFSET;
+use std::io::{ErrorKind, Read};
+use crate::schema::decode::{frame_len, verify_frame, DecodeError, MAX_FRAME_LEN};
+use crate::schema::monitoring_generated::workahub::monitoring::MonitoringPacket;
+
+// Monitoring packets are written size-prefixed (u32 little-endian length, then the
+// buffer carrying the "WHMP" identifier), so any number of them can be concatenated
+// into one stream or `.whmp` file and read back frame by frame.
+
+// Iterates over the packets in an in-memory buffer without copying them.
+// Iteration stops after the first error, since a bad length prefix leaves
+// no reliable way to find the next frame.
+pub struct PacketFrames<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> PacketFrames<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    fn next_frame(&mut self) -> Result<MonitoringPacket<'a>, DecodeError> {
+        let buf: &'a [u8] = self.buf;
+        let rest = &buf[self.pos..];
+        let len = frame_len(rest)?;
+        self.pos += len;
+        verify_frame(&rest[..len])
+    }
+}
+
+impl<'a> Iterator for PacketFrames<'a> {
+    type Item = Result<MonitoringPacket<'a>, DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.buf.len() {
+            return None;
+        }
+        let result = self.next_frame();
+        if result.is_err() {
+            self.pos = self.buf.len();
+        }
+        Some(result)
+    }
+}
+
+// Reads verified frames one at a time from a file or socket. Each item is the
+// full size-prefixed frame, which `decode_packet` turns back into plain structs.
+pub struct PacketStreamReader<R: Read> {
+    reader: R,
+    done: bool,
+}
+
+impl<R: Read> PacketStreamReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self { reader, done: false }
+    }
+
+    // read_exact retries reads interrupted by a signal. It doesn't say how much
+    // of a short read arrived, so `Truncated` counts the bytes read before it.
+    fn read_frame(&mut self) -> Result<Option<Vec<u8>>, DecodeError> {
+        let mut prefix = [0u8; SIZE_UOFFSET];
+        // The stream may only end between frames
+        loop {
+            match self.reader.read(&mut prefix[..1]) {
+                Ok(0) => return Ok(None),
+                Ok(_) => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        fill(&mut self.reader, &mut prefix[1..], SIZE_UOFFSET, 1)?;
+
+        let len = u32::from_le_bytes(prefix) as usize;
+        if len > MAX_FRAME_LEN {
+            return Err(DecodeError::FrameTooLarge(len));
+        }
+        let mut frame = vec![0u8; SIZE_UOFFSET + len];
+        frame[..SIZE_UOFFSET].copy_from_slice(&prefix);
+
+        let needed = frame.len();
+        fill(&mut self.reader, &mut frame[SIZE_UOFFSET..], needed, SIZE_UOFFSET)?;
+
+        verify_frame(&frame)?;
+        Ok(Some(frame))
+    }
+}
+
+// Fills `buf`, reporting a stream that ends first as a frame of `needed` bytes
+// cut off after `available`
+fn fill<R: Read>(reader: &mut R, buf: &mut [u8], needed: usize, available: usize) -> Result<(), DecodeError> {
+    reader.read_exact(buf).map_err(|e| match e.kind() {
+        ErrorKind::UnexpectedEof => DecodeError::Truncated { needed, available },
+        _ => DecodeError::Io(e),
+    })
+}
+
+impl<R: Read> Iterator for PacketStreamReader<R> {
+    type Item = Result<Vec<u8>, DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.read_frame() {
+            Ok(Some(frame)) => Some(Ok(frame)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::monitor::{encode_monitoring_packet, InputStats, SystemStats};
+    use crate::schema::decode::{decode_packet, DecodedPacket};
+    use std::io::Cursor;
+
+    fn packet(timestamp: i64) -> Vec<u8> {
+        let input = InputStats { mouse_clicks: 1, key_presses: 2, mouse_moves: 3 };
+        let system = SystemStats { cpu_usage: 50.0, memory_used: 1 << 30, memory_total: 4 << 30 };
+        encode_monitoring_packet(&input, &system, timestamp)
+    }
+
+    fn two_packets() -> Vec<u8> {
+        let mut stream = packet(1);
+        stream.extend_from_slice(&packet(2));
+        stream
+    }
+
+    // Hands out one byte per read, failing with Interrupted before each
+    struct InterruptingReader {
+        inner: Cursor<Vec<u8>>,
+        interrupt: bool,
+    }
+
+    impl Read for InterruptingReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.interrupt = !self.interrupt;
+            if self.interrupt {
+                return Err(ErrorKind::Interrupted.into());
+            }
+            let len = buf.len().min(1);
+            self.inner.read(&mut buf[..len])
+        }
+    }
+
+    #[test]
+    fn frames_split_concatenated_packets() {
+        let stream = two_packets();
+        let timestamps: Vec<i64> = PacketFrames::new(&stream)
+            .map(|packet| DecodedPacket::try_from(packet.unwrap()).unwrap().timestamp)
+            .collect();
+        assert_eq!(timestamps, [1, 2]);
+    }
+
+    #[test]
+    fn reader_splits_concatenated_packets() {
+        let timestamps: Vec<i64> = PacketStreamReader::new(Cursor::new(two_packets()))
+            .map(|frame| decode_packet(&frame.unwrap()).unwrap().timestamp)
+            .collect();
+        assert_eq!(timestamps, [1, 2]);
+    }
+
+    #[test]
+    fn reader_retries_interrupted_reads() {
+        let reader = InterruptingReader { inner: Cursor::new(two_packets()), interrupt: false };
+        let frames: Vec<_> = PacketStreamReader::new(reader).collect::<Result<_, _>>().unwrap();
+        assert_eq!(frames.len(), 2);
+    }
+
+    #[test]
+    fn reader_reports_truncated_stream() {
+        let stream = packet(1);
+        let mut frames = PacketStreamReader::new(Cursor::new(stream[..stream.len() - 1].to_vec()));
+        assert!(matches!(
+            frames.next(),
+            Some(Err(DecodeError::Truncated { needed, available: SIZE_UOFFSET })) if needed == stream.len()
+        ));
+        assert!(frames.next().is_none());
+
+        let mut frames = PacketStreamReader::new(Cursor::new(stream[..2].to_vec()));
+        assert!(matches!(frames.next(), Some(Err(DecodeError::Truncated { needed: SIZE_UOFFSET, available: 1 }))));
+    }
+}
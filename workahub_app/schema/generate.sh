@@ -0,0 +1,25 @@
+#!/usr/bin/env bash
+# Regenerates the FlatBuffers bindings from monitoring.fbs:
+#   rust/src/schema/monitoring_generated.rs
+#   lib/src/flatbuffers/monitoring_workahub.monitoring_generated.dart
+# Commit the results together with the schema change. Uses flatc from $PATH
+# or $FLATC; it must match the `flatbuffers` crate version in rust/Cargo.toml,
+# since other releases format their output differently.
+set -euo pipefail
+
+FLATC_VERSION="25.12.19"
+FLATC="${FLATC:-flatc}"
+cd "$(dirname "$0")/.."
+
+if ! version="$("$FLATC" --version 2>/dev/null)"; then
+    echo "$FLATC not found; install flatc $FLATC_VERSION or set \$FLATC" >&2
+    exit 1
+fi
+if [[ "$version" != *"$FLATC_VERSION"* ]]; then
+    echo "$FLATC is $version, expected $FLATC_VERSION" >&2
+    exit 1
+fi
+
+"$FLATC" --rust -o rust/src/schema schema/monitoring.fbs
+"$FLATC" --dart -o lib/src/flatbuffers schema/monitoring.fbs
+echo "Regenerated the Rust and Dart bindings from schema/monitoring.fbs"